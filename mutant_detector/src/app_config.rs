@@ -1,3 +1,4 @@
+use crate::results::OutputFormat;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt, Clone)]
@@ -26,8 +27,90 @@ pub struct AppConfig {
     /// Stops execution upon encountering an error
     pub halt_on_error: bool,
 
+    #[structopt(long)]
+    /// Saturate a separate e-graph for each subject, holding only the nodes
+    /// reachable from it, so each subject gets its own iteration, node and time
+    /// limits
+    pub isolate_subjects: bool,
+
+    #[structopt(long, default_value = "1")]
+    /// Number of worker threads. Subject files are spread over the workers, or,
+    /// with --isolate-subjects, the subjects of each file. Zero uses one worker
+    /// per core.
+    pub jobs: usize,
+
+    #[structopt(long)]
+    /// Leave out the rules tagged unsound (see the verify-rules subcommand)
+    pub exclude_unsound_rules: bool,
+
+    #[structopt(long, use_delimiter = true)]
+    /// Comma separated groups of built-in rules to use, out of arithmetic,
+    /// division, remainder, ordering, comparison, boolean, bitwise, shift, phi,
+    /// heap, loop and call. Defaults to all of them
+    pub enable_rules: Option<Vec<String>>,
+
+    #[structopt(long, use_delimiter = true)]
+    /// Comma separated groups of built-in rules to leave out
+    pub disable_rules: Vec<String>,
+
+    #[structopt(long)]
+    /// File amending the built-in table of pure JDK methods, with lines
+    /// `pure Class.method` or `impure Class.method`
+    pub purity_table: Option<String>,
+
+    #[structopt(long)]
+    /// File of additional rewrite rules, one per line:
+    /// `NAME sound|unsound: LHS => RHS [if CONDITION(?a), ...]`. A rule named
+    /// like a built-in rule takes its place
+    pub rules: Option<String>,
+
+    #[structopt(long, requires = "rules")]
+    /// Use only the rules from --rules, instead of adding them to the built-in
+    /// rules
+    pub replace_builtin_rules: bool,
+
+    #[structopt(long)]
+    /// Major's mutants.log for the subject file, to break the equivalent and
+    /// duplicate mutants found down by operator, method and line. Needs a
+    /// single subject file
+    pub mutants_log: Option<String>,
+
+    #[structopt(long)]
+    /// Write a `.explanation` file next to each `.equiv-class` file, giving
+    /// for each merged mutant the chain of rewrites, rule by rule, that merged
+    /// it with the original or another mutant
+    pub explain: bool,
+
+    #[structopt(long, default_value = "equiv-class")]
+    /// Format of the results: json, jsonl or csv files with a record per
+    /// subject are written alongside the .equiv-class files
+    pub format: OutputFormat,
+
     #[structopt(long = "output_dir", short = "o", default_value = "equivalence_results")]
     /// Directory for storing output equivalence files
     pub results_directory: String,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub enum Command {
+    /// Check every rewrite rule, including those of --rules, by evaluating both
+    /// of its sides under edge-case and random int, long, float, double,
+    /// boolean and String valuations, reporting counterexamples
+    VerifyRules {
+        #[structopt(long, default_value = "1000")]
+        /// Number of random valuations per rule and type
+        samples: usize,
+
+        #[structopt(long, default_value = "0")]
+        /// Seed for the random valuations
+        seed: u64,
+
+        #[structopt(long)]
+        /// Check only the rules of --rules
+        user_rules_only: bool,
+    },
 }
 
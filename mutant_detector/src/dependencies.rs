@@ -2,9 +2,18 @@ pub mod app_config;
 pub mod execution_config;
 pub mod runtime_metrics;
 pub mod peg;
+pub mod interval;
+pub mod purity;
 pub mod rewrites;
+pub mod rule_file;
 pub mod primitives;
 pub mod driver;
+pub mod subsumption;
+pub mod explain;
+pub mod results;
+pub mod mutants_log;
+pub mod error;
+pub mod verify;
 
 #[macro_use]
 extern crate log;
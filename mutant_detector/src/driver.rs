@@ -1,107 +1,376 @@
+use crate::error::{DetectorError, Location};
+use crate::explain::{explain, recording_rewrites, snapshot, ProofLog, Proofs};
 use crate::execution_config::ExecutionConfig;
 use crate::runtime_metrics::RuntimeMetrics;
 use crate::peg::{Peg, PegAnalysis};
 use crate::rewrites::RewriteSystem;
+use crate::subsumption::{mutation_points, subsumption_graph, MutationPoint, SubsumptionGraph};
 use egg::*;
-use serde::Deserialize;
-use serde_aux::prelude::*;
-use serde_xml_rs::from_reader;
 use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use xml::common::Position;
+use xml::reader::{EventReader, XmlEvent};
 
-#[derive(Debug, Deserialize)]
-#[serde(rename = "node_equivalence")]
+/// The `version` attribute of the `<subjects>` root element that this loader
+/// understands. Files written before the serializer stamped a version are read
+/// as this version.
+pub const SUBJECTS_SCHEMA_VERSION: &str = "1";
+
+#[derive(Debug)]
 pub struct NodeEquivalence {
     pub first: String,
     pub second: String,
 }
 
-#[derive(Debug, Deserialize, Default)]
-#[serde(rename = "node_equivalences")]
-pub struct NodeEquivalences {
-    #[serde(rename = "node_equivalence", default)]
-    pub equivalences: Vec<NodeEquivalence>,
-}
-
-#[derive(Debug, Deserialize)]
+/// The subjects of a single subject file together with the e-graph holding
+/// its `<id_table>`. Table rows are added to `egraph` as they are read, so the
+/// table itself is never materialized---unless subjects are analyzed in
+/// isolation, in which case the rows are kept in `id_table` instead and
+/// `egraph` stays empty.
+#[derive(Default)]
 pub struct Subjects {
-    #[serde(rename = "subject", default)]
+    /// The subject file these subjects were read from
+    pub file: String,
     pub subjects: Vec<Subject>,
-    pub identifier_table: IdTable,
-    #[serde(default)]
-    pub equivalences: NodeEquivalences,
-    #[serde(skip)]
+    pub egraph: EGraph<Peg, PegAnalysis>,
+    /// Maps serializer ids (the `id` attribute of a `dedup_entry`) to e-class
+    /// ids in `egraph`
     pub id_mapping: HashMap<Id, Id>,
+    pub id_table: Option<IdTable>,
+    /// Subjects that couldn't be analyzed, removed from `subjects`
+    pub skipped: Vec<(Subject, DetectorError)>,
+    highest_raw_id: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The parsed rows of an `<id_table>`, keyed by serializer id. Children still
+/// refer to serializer ids.
+#[derive(Debug, Default)]
 pub struct IdTable {
-    #[serde(rename = "dedup_entry")]
-    pub entries: Vec<IdEntry>,
+    pub entries: HashMap<Id, Peg>,
+    pub equivalences: Vec<(Id, Id)>,
 }
 
-#[derive(Debug, Default, Deserialize)]
+impl IdTable {
+    /// Build an e-graph holding only the rows reachable from `roots`, along
+    /// with the mapping from serializer ids to e-class ids in it. A row is
+    /// reachable through its children and through its equivalences, which is
+    /// how a theta's blank reaches the loop body it continues with
+    pub fn slice(&self, roots: impl Iterator<Item = Id>) -> (EGraph<Peg, PegAnalysis>, HashMap<Id, Id>) {
+        let mut equivalents = HashMap::<Id, Vec<Id>>::new();
+        for &(fst, snd) in &self.equivalences {
+            equivalents.entry(fst).or_default().push(snd);
+            equivalents.entry(snd).or_default().push(fst);
+        }
+        let mut reachable = HashSet::new();
+        let mut worklist: Vec<Id> = roots.filter(|id| self.entries.contains_key(id)).collect();
+        while let Some(id) = worklist.pop() {
+            if reachable.insert(id) {
+                self.entries[&id].for_each(|child| worklist.push(child));
+                let equivalent = equivalents.get(&id).into_iter().flatten();
+                worklist.extend(equivalent.filter(|id| self.entries.contains_key(id)));
+            }
+        }
+
+        // Rows only refer to rows with smaller ids, so adding them in id order
+        // always finds the children already in the e-graph
+        let mut reachable: Vec<Id> = reachable.into_iter().collect();
+        reachable.sort();
+
+        let mut egraph = EGraph::<Peg, PegAnalysis>::default();
+        let mut id_mapping = HashMap::<Id, Id>::new();
+        for raw_id in reachable {
+            let mut node = self.entries[&raw_id].clone();
+            node.for_each_mut(|child| *child = id_mapping[child]);
+            let egg_id = egraph.add(node);
+            id_mapping.insert(raw_id, egg_id);
+        }
+        for (fst, snd) in &self.equivalences {
+            if let (Some(&id1), Some(&id2)) = (id_mapping.get(fst), id_mapping.get(snd)) {
+                egraph.union(id1, id2);
+            }
+        }
+        (egraph, id_mapping)
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct AnalysisResult {
     pub score: u32,
     pub equivalence_classes: Vec<HashSet<u32>>,
+    /// The mutants in the original's class, in increasing order
+    pub equivalent_to_original: Vec<u32>,
+    /// The classes of several mutants but not the original, each in increasing
+    /// order: all but one of each group are redundant
+    pub duplicate_groups: Vec<Vec<u32>>,
+    /// Why saturation stopped for the e-graph this subject was analyzed in
+    pub stop_reason: Option<StopReason>,
+    /// Saturation iterations run on that e-graph
+    pub iterations: usize,
+    /// Nodes in that e-graph once saturation stopped
+    pub egraph_nodes: usize,
+    /// Seconds spent saturating that e-graph
+    pub saturation_time: f64,
+    pub subsumption: SubsumptionGraph,
+    /// Why mutants were merged, with `--explain`
+    pub explanation: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct IdEntry {
-    pub identifier: String,
-    pub peg_representation: String,
+impl AnalysisResult {
+    /// The mutants counted by `score`: all but the smallest id of each class,
+    /// so every mutant of the original's class
+    pub fn merged_mutants(&self) -> Vec<u32> {
+        let mut merged: Vec<u32> = self.equivalence_classes.iter()
+            .flat_map(|class| {
+                let smallest = class.iter().min().copied();
+                class.iter().copied().filter(move |&mid| Some(mid) != smallest)
+            })
+            .collect();
+        merged.sort();
+        merged
+    }
 }
 
-
 impl Subjects {
+    /// Add a `<dedup_entry id="..." peg="..."/>` row of the serializer's
+    /// `<id_table>` to the e-graph. Rows must arrive in increasing id order and
+    /// may only refer to rows that precede them.
+    pub fn add_id_entry(&mut self, at: &Location, id: &str, peg: &str) -> Result<(), DetectorError> {
+        let raw_id = id.parse::<usize>().map_err(|_| DetectorError::Parse {
+            at: at.clone(),
+            msg: format!("identifier '{}' is not a non-negative integer", id),
+        })?;
+        if let Some(highest_raw_id) = self.highest_raw_id {
+            if raw_id <= highest_raw_id {
+                return Err(DetectorError::NonMonotonicId { at: at.clone(), id: raw_id, previous: highest_raw_id });
+            }
+        }
+        self.highest_raw_id = Some(raw_id);
+
+        let mut peg_instance: Peg = parse_peg_from_string(peg)
+            .map_err(|reason| DetectorError::UnknownOperator {
+                at: at.clone(),
+                id: raw_id,
+                peg: peg.to_string(),
+                reason,
+            })?;
+        if let Some(&child_id) = peg_instance.children().iter().find(|child| !self.is_defined(child)) {
+            return Err(DetectorError::DanglingChildId { at: at.clone(), id: raw_id, child: child_id.into() });
+        }
 
-    pub fn compute_expression(&mut self) -> Result<RecExpr<Peg>, String> {
-        info!("Starting computation of RecExpr");
-        let mut rec_expression = RecExpr::default();
-        let mut highest_raw_id = -1;
-
-        for entry in self.identifier_table.entries.iter() {
-            let raw_id_int = entry.identifier.parse::<i32>()
-                    .unwrap_or_else(|_| panic!("Failed to parse identifier '{}' as u32", &entry.identifier));
-            if raw_id_int <= highest_raw_id {
-                return Err(format!("Error: Identifier {} <= previous identifier {}", raw_id_int, highest_raw_id));
+        match self.id_table.as_mut() {
+            Some(table) => {
+                table.entries.insert(Id::from(raw_id), peg_instance);
             }
-            if raw_id_int < 0 {
-                return Err(format!("Error: Identifier {} is less than 0", raw_id_int));
+            None => {
+                let id_mapping = &self.id_mapping;
+                peg_instance.for_each_mut(|child_id: &mut Id| *child_id = id_mapping[child_id]);
+                let egg_id = self.egraph.add(peg_instance);
+                self.id_mapping.insert(Id::from(raw_id), egg_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the serializer id `raw_id` has been read from the id table
+    fn is_defined(&self, raw_id: &Id) -> bool {
+        match &self.id_table {
+            Some(table) => table.entries.contains_key(raw_id),
+            None => self.id_mapping.contains_key(raw_id),
+        }
+    }
+
+    /// Union the e-classes of two serializer ids that the serializer found to
+    /// be equal
+    pub fn add_equivalence(&mut self, at: &Location, equivalence: &NodeEquivalence) -> Result<(), DetectorError> {
+        let lookup = |raw: &str| {
+            raw.parse::<usize>().ok()
+                .map(Id::from)
+                .filter(|raw_id| self.is_defined(raw_id))
+                .ok_or_else(|| DetectorError::Parse {
+                    at: at.clone(),
+                    msg: format!("node equivalence refers to id '{}', which is not in the id table", raw),
+                })
+        };
+        let (fst, snd) = (lookup(&equivalence.first)?, lookup(&equivalence.second)?);
+        match self.id_table.as_mut() {
+            Some(table) => table.equivalences.push((fst, snd)),
+            None => {
+                let (id1, id2) = (self.id_mapping[&fst], self.id_mapping[&snd]);
+                self.egraph.union(id1, id2);
             }
-            highest_raw_id = raw_id_int;
-            let internal_id = Id::from(raw_id_int as usize);
-            
-            let mut peg_instance: Peg = parse_peg_from_string(entry.peg_representation.clone())?;
-            let children = peg_instance.children_mut();
-            let children_ids: Vec<_> = peg_instance.children().iter().cloned().collect();
-
-            for (i, child_id) in children_ids.iter().enumerate() {
-                if let Some(&mapped_id) = self.id_mapping.get(child_id) {
-                    peg_instance.children_mut()[i] = mapped_id;
-                }
-            }            
-            let egg_internal_id = rec_expression.add(peg_instance);
-            self.id_mapping.insert(internal_id, egg_internal_id);
         }
-        info!("RecExpr computation finished");
-        Ok(rec_expression)
+        Ok(())
     }
 
     pub fn total_mutants(&self) -> usize {
         self.subjects.iter().map(|subject| subject.mutants.len()).sum()
     }
+
+    /// Stream the document written by the serializer's `XMLGenerator`:
+    ///
+    /// ```xml
+    /// <subjects version="1">
+    ///   <subject sourcefile="..." method="...">
+    ///     <pid>12</pid>
+    ///     <mutant mid="1" pid="17"/>
+    ///   </subject>
+    ///   <id_table>
+    ///     <dedup_entry id="0" peg="(unit)"/>
+    ///   </id_table>
+    ///   <node_equivalences>
+    ///     <node_equivalence><first>12</first><second>68</second></node_equivalence>
+    ///   </node_equivalences>
+    /// </subjects>
+    /// ```
+    ///
+    /// Only one element is held in memory at a time: `dedup_entry` rows go
+    /// straight into the e-graph (or into `id_table` when `isolate_subjects`
+    /// is set), so peak memory tracks the size of the id table rather than the
+    /// size of the document. Any element or attribute that doesn't fit this
+    /// shape is reported by name, together with its position in the file.
+    pub fn from_reader<R: Read>(file: &str, source: R, isolate_subjects: bool) -> Result<Subjects, DetectorError> {
+        let mut parser = EventReader::new(source);
+        let mut subjects = Subjects {
+            file: file.to_string(),
+            id_table: if isolate_subjects { Some(IdTable::default()) } else { None },
+            ..Subjects::default()
+        };
+        // Names of the currently open elements, outermost first
+        let mut open_elements: Vec<String> = vec![];
+        let mut current_subject: Option<Subject> = None;
+        let mut current_pid: Option<String> = None;
+        let mut first: Option<String> = None;
+        let mut second: Option<String> = None;
+        let mut text = String::new();
+        let mut seen_id_table = false;
+        // The serializer writes `<node_equivalences>` after `<id_table>`, but
+        // nothing in the schema requires it, so unions wait for the whole table
+        let mut equivalences: Vec<(Location, NodeEquivalence)> = vec![];
+
+        loop {
+            let event = parser.next().map_err(|e| DetectorError::Parse {
+                at: Location::line(file, e.position().row + 1),
+                msg: format!("malformed XML: {}", e.msg()),
+            })?;
+            let pos = parser.position();
+            let at = Location::line(file, pos.row + 1);
+            let schema_error = |element: &str, msg: &str| DetectorError::Parse {
+                at: at.clone(),
+                msg: format!("<{}> at column {}: {}", element, pos.column + 1, msg),
+            };
+
+            match event {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    let element = name.local_name;
+                    let attribute = |attr: &str| {
+                        attributes.iter()
+                            .find(|a| a.name.local_name == attr)
+                            .map(|a| a.value.clone())
+                            .ok_or_else(|| schema_error(&element, &format!("missing attribute '{}'", attr)))
+                    };
+                    let parent = open_elements.last().map(String::as_str);
+                    match (parent, element.as_str()) {
+                        (None, "subjects") => {
+                            let version = attributes.iter()
+                                .find(|a| a.name.local_name == "version")
+                                .map_or(SUBJECTS_SCHEMA_VERSION, |a| a.value.as_str());
+                            if version != SUBJECTS_SCHEMA_VERSION {
+                                return Err(schema_error(&element, &format!(
+                                    "unsupported schema version '{}' (expected '{}')", version, SUBJECTS_SCHEMA_VERSION)));
+                            }
+                        }
+                        (None, _) => return Err(schema_error(&element, "expected <subjects> as the root element")),
+                        (Some("subjects"), "subject") => {
+                            current_subject = Some(Subject {
+                                source_file: attribute("sourcefile")?,
+                                method: attribute("method")?,
+                                pid: String::new(),
+                                mutants: vec![],
+                                analysis_result: AnalysisResult::default(),
+                            });
+                            current_pid = None;
+                        }
+                        (Some("subjects"), "id_table") => seen_id_table = true,
+                        (Some("subjects"), "node_equivalences") => (),
+                        (Some("subject"), "pid") if current_pid.is_none() => text.clear(),
+                        (Some("subject"), "mutant") => {
+                            let mid = attribute("mid")?;
+                            let mid = mid.parse::<u32>()
+                                .map_err(|_| schema_error(&element, &format!("mutant id '{}' is not a number", mid)))?;
+                            let pid = attribute("pid")?;
+                            if let Some(subject) = current_subject.as_mut() {
+                                subject.mutants.push(Mutant { mid, pid });
+                            }
+                        }
+                        (Some("id_table"), "dedup_entry") => {
+                            subjects.add_id_entry(&at, &attribute("id")?, &attribute("peg")?)?;
+                        }
+                        (Some("node_equivalences"), "node_equivalence") => {
+                            first = None;
+                            second = None;
+                        }
+                        (Some("node_equivalence"), "first") if first.is_none() => text.clear(),
+                        (Some("node_equivalence"), "second") if second.is_none() => text.clear(),
+                        (Some(parent), _) => {
+                            return Err(schema_error(&element, &format!("unexpected element in <{}>", parent)));
+                        }
+                    }
+                    open_elements.push(element);
+                }
+                XmlEvent::Characters(chars) | XmlEvent::CData(chars) => text.push_str(&chars),
+                XmlEvent::EndElement { name } => {
+                    let element = name.local_name;
+                    let required_text = || match text.trim() {
+                        "" => Err(schema_error(&element, "expected text content")),
+                        trimmed => Ok(trimmed.to_string()),
+                    };
+                    match element.as_str() {
+                        "pid" => current_pid = Some(required_text()?),
+                        "first" => first = Some(required_text()?),
+                        "second" => second = Some(required_text()?),
+                        "subject" => {
+                            let mut subject = current_subject.take()
+                                .ok_or_else(|| schema_error(&element, "unbalanced element"))?;
+                            subject.pid = current_pid.take()
+                                .ok_or_else(|| schema_error(&element, "missing <pid> element"))?;
+                            subjects.subjects.push(subject);
+                        }
+                        "node_equivalence" => equivalences.push((at.clone(), NodeEquivalence {
+                            first: first.take().ok_or_else(|| schema_error(&element, "missing <first> element"))?,
+                            second: second.take().ok_or_else(|| schema_error(&element, "missing <second> element"))?,
+                        })),
+                        _ => (),
+                    }
+                    text.clear();
+                    open_elements.pop();
+                }
+                XmlEvent::EndDocument => break,
+                _ => (),
+            }
+        }
+
+        if !seen_id_table {
+            return Err(DetectorError::Parse { at: Location::file(file), msg: "missing <id_table> element".to_string() });
+        }
+        for (at, equivalence) in &equivalences {
+            subjects.add_equivalence(at, equivalence)?;
+        }
+        Ok(subjects)
+    }
 }
 
-fn parse_peg_from_string(peg_str: String) -> Result<Peg, String> {
+fn parse_peg_from_string(peg_str: &str) -> Result<Peg, String> {
     let formatted_peg_str = match peg_str.chars().next() {
         Some('(') if peg_str.ends_with(')') => &peg_str[1..peg_str.len() - 1],
-        Some('"') if peg_str.ends_with('"') && peg_str.len() > 1 => {
-            return Ok(Peg::Symbol(Symbol::from(&peg_str[1..peg_str.len() - 1])));
-        },
-        _ if !peg_str.starts_with('(') && !peg_str.starts_with('"') => &peg_str,
+        _ if !peg_str.starts_with('(') => peg_str,
         _ => return Err(format!("Invalid PEG string format: {}", peg_str)),
     };
 
+    // String and char literals are leaves, and may contain whitespace
+    if formatted_peg_str.starts_with('"') || formatted_peg_str.starts_with('\'') {
+        return Peg::from_op_str(formatted_peg_str, vec![]);
+    }
+
     let parts: Vec<&str> = formatted_peg_str.split_whitespace().collect();
     let operator = parts.get(0).ok_or_else(|| "PEG string is empty".to_string())?;
     let children = parts[1..]
@@ -114,15 +383,12 @@ fn parse_peg_from_string(peg_str: String) -> Result<Peg, String> {
     Peg::from_op_str(operator, children)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct Subject {
-    #[serde(rename = "sourcefile")]
     pub source_file: String,
     pub method: String,
     pub pid: String,
-    #[serde(rename = "mutant")]
     pub mutants: Vec<Mutant>,
-    #[serde(default)]
     pub analysis_result: AnalysisResult,
 }
 
@@ -148,16 +414,15 @@ impl Subject {
         }
     }
 
-    pub fn from_file(path: String) -> Result<Subjects, String> {
-        use std::fs;
+    pub fn from_file(path: String, isolate_subjects: bool) -> Result<Subjects, DetectorError> {
+        use std::fs::File;
+        use std::io::BufReader;
         info!("Reading subject file from path: {}", path);
 
-        let contents = fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read file at '{}': {}", path, e))?;
-        info!("Subject file contents loaded successfully.");
+        let file = File::open(&path)
+            .map_err(|error| DetectorError::Io { at: Location::file(&path), error })?;
 
-        let subjects: Subjects = from_reader(contents.as_bytes())
-            .map_err(|e| format!("Failed to parse subjects from file '{}': {}", path, e))?;
+        let subjects = Subjects::from_reader(&path, BufReader::new(file), isolate_subjects)?;
         info!("Subjects parsed successfully.");
 
         Ok(subjects)
@@ -165,9 +430,8 @@ impl Subject {
 }
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct Mutant {
-    #[serde(deserialize_with = "deserialize_number_from_string")]
     pub mid: u32,
     pub pid: String,
 }
@@ -176,17 +440,20 @@ pub fn parse_and_run(
     subj_file: &str,
     run_config: &ExecutionConfig,
     global_data: &mut RuntimeMetrics,
-) -> Result<Subjects, String> {
+) -> Result<Subjects, DetectorError> {
     let trimmed_file_path = subj_file.trim();
-    let rewrite_rules = crate::rewrites::rw_rules();
     info!("Processing subject file: {}", trimmed_file_path);
     global_data.increment_subject_files();
 
-    let subjects = Subject::from_file(trimmed_file_path.to_string())
-        .map_err(|e| format!("Failed to process subjects from file '{}': {}", trimmed_file_path, e))?;
+    let subjects = Subject::from_file(trimmed_file_path.to_string(), run_config.isolate_subjects)?;
     info!("Successfully loaded subjects.");
 
-    run_on_subjects(subjects, &rewrite_rules, run_config, global_data)
+    if subjects.id_table.is_some() && run_config.jobs > 1 {
+        run_isolated_in_parallel(subjects, run_config, global_data)
+    } else {
+        let rewrite_rules = crate::rewrites::rw_rules_for(run_config);
+        run_on_subjects(subjects, &rewrite_rules, run_config, global_data)
+    }
 }
 
 pub fn run_on_subjects(
@@ -194,101 +461,425 @@ pub fn run_on_subjects(
     rules: &RewriteSystem,
     run_config: &ExecutionConfig,
     global_data: &mut RuntimeMetrics,
-) -> Result<Subjects, String> {
+) -> Result<Subjects, DetectorError> {
     global_data.record_new_subjects(subjects.subjects.len() as u32);
     global_data.record_new_mutants(subjects.total_mutants() as u32);
 
-    let rec_expr = subjects.compute_expression()
-        .map_err(|e| format!("Failed to compute RecExpr: {}", e))?;
+    let at = Location::file(&subjects.file);
+    let mut analyzed = vec![];
+    let to_analyze = std::mem::take(&mut subjects.subjects);
+    if let Some(table) = &subjects.id_table {
+        for mut subj in to_analyze {
+            let result = analyze_in_isolation(&mut subj, table, rules, run_config, &at);
+            global_data.record_stop_reason(&subj.analysis_result.stop_reason);
+            match result {
+                Ok(()) => analyzed.push(subj),
+                Err(e) => subjects.skipped.push((subj, e)),
+            }
+        }
+    } else {
+        let points: Vec<_> = to_analyze.iter()
+            .map(|subj| mutation_points(subj, &subjects.egraph, &subjects.id_mapping))
+            .collect();
+        let egraph = std::mem::take(&mut subjects.egraph);
+        let (runner, proofs) = if run_config.explain {
+            let (runner, proofs) = saturate_explained(egraph, run_config);
+            (runner, Some(proofs))
+        } else {
+            (saturate(egraph, rules, run_config), None)
+        };
+        global_data.record_stop_reason(&runner.stop_reason);
+
+        for (mut subj, points) in to_analyze.into_iter().zip(&points) {
+            match analyze_subject(&mut subj, &runner, &subjects.id_mapping, points, &at) {
+                Ok(()) => {
+                    if let Some(proofs) = &proofs {
+                        subj.analysis_result.explanation = Some(explain(&subj, points, &subjects.id_mapping, proofs));
+                    }
+                    analyzed.push(subj)
+                }
+                Err(e) => subjects.skipped.push((subj, e)),
+            }
+        }
+    }
+    subjects.subjects = analyzed;
+
+    Ok(subjects)
+}
 
-    let mut egraph = EGraph::<Peg, PegAnalysis>::default();
-    let mut id_offset_map = HashMap::<Id, Id>::new();
+/// Like `run_on_subjects` for isolated subjects, but spreads the subjects over
+/// `run_config.jobs` worker threads. Each worker builds its own rewrite rules;
+/// subjects come back in their original order.
+fn run_isolated_in_parallel(
+    mut subjects: Subjects,
+    run_config: &ExecutionConfig,
+    global_data: &mut RuntimeMetrics,
+) -> Result<Subjects, DetectorError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
 
-    rec_expr.as_ref().iter().enumerate().try_for_each(|(idx, node)| {
-        let mut node = node.clone();
-        node.for_each_mut(|id: &mut Id| *id = *id_offset_map.get(id)
-            .expect(&format!("Node at idx {} has unbound child {}", idx, id)));
+    global_data.record_new_subjects(subjects.subjects.len() as u32);
+    global_data.record_new_mutants(subjects.total_mutants() as u32);
 
-        let id = egraph.add(node);
-        let canonical_id = egraph.find(id);
-        id_offset_map.insert(Id::from(idx), canonical_id);
-        Ok(())
-    }).map_err(|e: Box<dyn std::error::Error>| e.to_string())?;
-
-    subjects.equivalences.equivalences.iter().try_for_each(|equivalence| {
-        let fst_id = Id::from(equivalence.first.parse::<usize>().map_err(|_| format!("Couldn't parse ID {}", equivalence.first))?);
-        let snd_id = Id::from(equivalence.second.parse::<usize>().map_err(|_| format!("Couldn't parse ID {}", equivalence.second))?);
-
-        let (id1, id2) = (
-            id_offset_map.get(&fst_id).ok_or_else(|| format!("ID {} not found in id_offset_map", fst_id)),
-            id_offset_map.get(&snd_id).ok_or_else(|| format!("ID {} not found in id_offset_map", snd_id)),
-        );
-
-        match (id1, id2) {
-            (Ok(id1), Ok(id2)) => {
-                egraph.union(*id1, *id2);
-                Ok(())
-            },
-            _ => Err("Failed to find node IDs in the offset map.".to_string()),
+    let at = Location::file(&subjects.file);
+    let to_analyze: Vec<Mutex<Option<Subject>>> = std::mem::take(&mut subjects.subjects)
+        .into_iter()
+        .map(|subj| Mutex::new(Some(subj)))
+        .collect();
+    let next_subject = AtomicUsize::new(0);
+    let table = subjects.id_table.as_ref().expect("Isolated subjects must keep their id table");
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..run_config.jobs.min(to_analyze.len()) {
+            let sender = sender.clone();
+            let (to_analyze, next_subject, at) = (&to_analyze, &next_subject, &at);
+            scope.spawn(move || {
+                let rules = crate::rewrites::rw_rules_for(run_config);
+                loop {
+                    let index = next_subject.fetch_add(1, Ordering::SeqCst);
+                    let mut subj = match to_analyze.get(index) {
+                        Some(slot) => slot.lock().unwrap().take().unwrap(),
+                        None => break,
+                    };
+                    let result = analyze_in_isolation(&mut subj, table, &rules, run_config, at);
+                    if sender.send((index, subj, result)).is_err() {
+                        break;
+                    }
+                }
+            });
         }
-    })?;
+    });
+    drop(sender);
+
+    let mut results: Vec<_> = receiver.into_iter().collect();
+    results.sort_by_key(|(index, _, _)| *index);
+    for (_, subj, result) in results {
+        global_data.record_stop_reason(&subj.analysis_result.stop_reason);
+        match result {
+            Ok(()) => subjects.subjects.push(subj),
+            Err(e) => subjects.skipped.push((subj, e)),
+        }
+    }
 
-    let runner = Runner::default()
+    Ok(subjects)
+}
+
+/// Saturate and analyze `subj` in an e-graph of its own, holding only the rows
+/// of `table` reachable from its pids
+fn analyze_in_isolation(
+    subj: &mut Subject,
+    table: &IdTable,
+    rules: &RewriteSystem,
+    run_config: &ExecutionConfig,
+    at: &Location,
+) -> Result<(), DetectorError> {
+    let roots = std::iter::once(&subj.pid)
+        .chain(subj.mutants.iter().map(|mutant| &mutant.pid))
+        .filter_map(|pid| pid.parse::<usize>().ok())
+        .map(Id::from);
+    let (egraph, id_mapping) = table.slice(roots);
+    let points = mutation_points(subj, &egraph, &id_mapping);
+    let (runner, proofs) = if run_config.explain {
+        let (runner, proofs) = saturate_explained(egraph, run_config);
+        (runner, Some(proofs))
+    } else {
+        (saturate(egraph, rules, run_config), None)
+    };
+    subj.analysis_result.stop_reason = runner.stop_reason.clone();
+
+    analyze_subject(subj, &runner, &id_mapping, &points, at)?;
+    if let Some(proofs) = &proofs {
+        subj.analysis_result.explanation = Some(explain(subj, &points, &id_mapping, proofs));
+    }
+    Ok(())
+}
+
+fn saturate(
+    egraph: EGraph<Peg, PegAnalysis>,
+    rules: &RewriteSystem,
+    run_config: &ExecutionConfig,
+) -> Runner<Peg, PegAnalysis> {
+    Runner::default()
         .with_egraph(egraph)
         .with_iter_limit(run_config.max_iterations)
         .with_node_limit(run_config.max_nodes)
         .with_time_limit(run_config.execution_timeout)
         .with_scheduler(egg::SimpleScheduler)
-        .run(rules);
-
-    global_data.record_stop_reason(&runner.stop_reason);
-    
-    let id_mapping_ref = &subjects.id_mapping;
-    subjects.subjects.iter_mut().enumerate().for_each(|(_i, subj)| {
-        analyze_subject(subj, &runner.egraph, &rec_expr, id_mapping_ref, &id_offset_map);
-    });
+        .run(rules)
+}
 
-    Ok(subjects)
+/// Like `saturate`, with rewrites that record the merges they make, so that
+/// `explain` can give the rules that merged mutants
+fn saturate_explained(
+    egraph: EGraph<Peg, PegAnalysis>,
+    run_config: &ExecutionConfig,
+) -> (Runner<Peg, PegAnalysis>, Proofs) {
+    let log = ProofLog::default();
+    let rules = recording_rewrites(crate::rewrites::rules_for(run_config), &log);
+    let initial = snapshot(&egraph);
+    let runner = saturate(egraph, &rules, run_config);
+    let proofs = Proofs::new(&log, initial, &runner.egraph);
+    (runner, proofs)
 }
 
+/// Look up the e-class of a subject's (`mid` is `None`) or mutant's pid
+fn lookup_pid(
+    subj: &Subject,
+    mid: Option<u32>,
+    pid: &str,
+    raw_id_to_egg_id: &HashMap<Id, Id>,
+    at: &Location,
+) -> Result<Id, DetectorError> {
+    pid.parse::<usize>().ok()
+        .and_then(|raw_id| raw_id_to_egg_id.get(&Id::from(raw_id)).copied())
+        .ok_or_else(|| DetectorError::MissingSubjectPid {
+            at: at.clone(),
+            method: subj.method.clone(),
+            mid,
+            pid: pid.to_string(),
+        })
+}
 
 fn analyze_subject(
     subj: &mut Subject,
-    egraph: &EGraph<Peg, PegAnalysis>,
-    _expr: &RecExpr<Peg>,
+    runner: &Runner<Peg, PegAnalysis>,
     raw_id_to_egg_id: &HashMap<Id, Id>,
-    id_update: &HashMap<Id, Id>,
-) {
+    points: &HashMap<u32, MutationPoint>,
+    at: &Location,
+) -> Result<(), DetectorError> {
+    let egraph = &runner.egraph;
     let mut rev_can_id_lookup = HashMap::<Id, HashSet<u32>>::new();
-    let primary_id = Id::from(subj.pid.parse::<usize>().expect("Failed to parse subject pid"));
-    let primary_id = *raw_id_to_egg_id.get(&primary_id)
-        .expect("Subject pid not found in raw_id_to_egg_id mapping");
-    let canonical_primary_id = *id_update.get(&primary_id)
-        .expect("Subject pid not found in id_update mapping");
-    let canonical_primary_id = egraph.find(canonical_primary_id);
+    let primary_id = lookup_pid(subj, None, &subj.pid, raw_id_to_egg_id, at)?;
+    let canonical_primary_id = egraph.find(primary_id);
 
     rev_can_id_lookup.entry(canonical_primary_id).or_insert_with(HashSet::new).insert(0);
 
-    subj.mutants.iter().for_each(|mutant| {
-        let mutant_id = Id::from(mutant.pid.parse::<usize>().expect("Failed to parse mutant pid"));
-        let mutant_id = *raw_id_to_egg_id.get(&mutant_id)
-            .expect("Mutant pid not found in raw_id_to_egg_id mapping");
-        let canonical_mutant_id = *id_update.get(&mutant_id)
-            .expect("Mutant pid not found in id_update mapping");
-        let canonical_mutant_id = egraph.find(canonical_mutant_id);
+    for mutant in &subj.mutants {
+        let mutant_id = lookup_pid(subj, Some(mutant.mid), &mutant.pid, raw_id_to_egg_id, at)?;
+        let canonical_mutant_id = egraph.find(mutant_id);
 
         rev_can_id_lookup.entry(canonical_mutant_id).or_insert_with(HashSet::new).insert(mutant.mid);
-    });
+    }
 
     let mut num_equivalences = 0;
-    let equiv_classes: Vec<HashSet<u32>> = rev_can_id_lookup.values().cloned().collect();
+    let mut equiv_classes: Vec<HashSet<u32>> = rev_can_id_lookup.values().cloned().collect();
+    // Classes are disjoint, so their smallest ids give a stable order that
+    // doesn't depend on hashing
+    equiv_classes.sort_by_key(|class| class.iter().min().copied());
     num_equivalences = equiv_classes.iter().map(|class| class.len() as u32 - 1).sum();
 
+    let mut equivalent_to_original = vec![];
+    let mut duplicate_groups = vec![];
+    for class in &equiv_classes {
+        let mut mids: Vec<u32> = class.iter().copied().collect();
+        mids.sort();
+        if mids[0] == 0 {
+            equivalent_to_original = mids[1..].to_vec();
+        } else if mids.len() > 1 {
+            duplicate_groups.push(mids);
+        }
+    }
+    let subsumption = subsumption_graph(egraph, &equiv_classes, points);
+
     subj.analysis_result = AnalysisResult {
         score: num_equivalences,
-        equivalence_classes: equiv_classes, 
+        equivalence_classes: equiv_classes,
+        equivalent_to_original,
+        duplicate_groups,
+        stop_reason: runner.stop_reason.clone(),
+        iterations: runner.iterations.len(),
+        egraph_nodes: egraph.total_size(),
+        saturation_time: runner.iterations.iter().map(|iteration| iteration.total_time).sum(),
+        subsumption,
+        explanation: None,
     };
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUBJECTS: &str = r#"<subjects version="1">
+  <subject sourcefile="A.java" method="A@f(int)">
+    <pid>5</pid>
+    <mutant mid="1" pid="6"/>
+    <mutant mid="2" pid="7"/>
+  </subject>
+  <id_table>
+    <dedup_entry id="0" peg="(a)"/>
+    <dedup_entry id="1" peg="(nil)"/>
+    <dedup_entry id="2" peg="(var 0 1)"/>
+    <dedup_entry id="3" peg="1"/>
+    <dedup_entry id="4" peg="2"/>
+    <dedup_entry id="5" peg="(+ 2 3)"/>
+    <dedup_entry id="6" peg="(- 2 3)"/>
+    <dedup_entry id="7" peg="(+ 2 4)"/>
+  </id_table>
+  <node_equivalences>
+    <node_equivalence><first>5</first><second>7</second></node_equivalence>
+  </node_equivalences>
+</subjects>"#;
+
+    fn load(xml: &str, isolate_subjects: bool) -> Result<Subjects, DetectorError> {
+        Subjects::from_reader("test.xml", xml.as_bytes(), isolate_subjects)
+    }
+
+    fn parse_error(xml: &str) -> String {
+        match load(xml, false) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("expected an error loading {}", xml),
+        }
+    }
+
+    #[test]
+    fn loads_subjects_and_mutants() {
+        let subjects = load(SUBJECTS, false).unwrap();
+        assert_eq!(subjects.file, "test.xml");
+        assert_eq!(subjects.subjects.len(), 1);
+        let subject = &subjects.subjects[0];
+        assert_eq!(subject.source_file, "A.java");
+        assert_eq!(subject.method, "A@f(int)");
+        assert_eq!(subject.pid, "5");
+        let mutants: Vec<(u32, &str)> = subject.mutants.iter().map(|m| (m.mid, m.pid.as_str())).collect();
+        assert_eq!(mutants, vec![(1, "6"), (2, "7")]);
+        assert_eq!(subjects.total_mutants(), 2);
+    }
+
+    #[test]
+    fn adds_rows_to_the_egraph_and_unions_equivalences() {
+        let subjects = load(SUBJECTS, false).unwrap();
+        assert!(subjects.id_table.is_none());
+        assert_eq!(subjects.id_mapping.len(), 8);
+        let class = |raw_id: usize| subjects.egraph.find(subjects.id_mapping[&Id::from(raw_id)]);
+        assert_eq!(class(5), class(7));
+        assert_ne!(class(5), class(6));
+    }
+
+    #[test]
+    fn keeps_rows_in_the_id_table_when_isolating_subjects() {
+        let subjects = load(SUBJECTS, true).unwrap();
+        let table = subjects.id_table.as_ref().unwrap();
+        assert_eq!(table.entries.len(), 8);
+        assert_eq!(table.equivalences, vec![(Id::from(5), Id::from(7))]);
+        assert!(subjects.id_mapping.is_empty());
+
+        // A slice holds only the rows reachable from its roots
+        let (egraph, id_mapping) = table.slice(vec![Id::from(6)].into_iter());
+        assert_eq!(id_mapping.len(), 5);
+        assert!(!id_mapping.contains_key(&Id::from(4)));
+        assert_eq!(egraph.number_of_classes(), 5);
+
+        // Equivalent rows are reachable too, as a theta's blank reaches its
+        // continuation
+        let (egraph, id_mapping) = table.slice(vec![Id::from(5)].into_iter());
+        assert_eq!(id_mapping.len(), 7);
+        assert_eq!(egraph.find(id_mapping[&Id::from(5)]), egraph.find(id_mapping[&Id::from(7)]));
+    }
+
+    #[test]
+    fn reads_files_without_a_version_or_subjects() {
+        let subjects = load(r#"<subjects><id_table><dedup_entry id="0" peg="(unit)"/></id_table></subjects>"#, false)
+            .unwrap();
+        assert!(subjects.subjects.is_empty());
+        assert_eq!(subjects.id_mapping.len(), 1);
+    }
+
+    #[test]
+    fn reads_literals_with_whitespace() {
+        let subjects = load(r#"<subjects><id_table><dedup_entry id="0" peg="(&quot;a b&quot;)"/><dedup_entry id="1" peg="' '"/></id_table></subjects>"#, false)
+            .unwrap();
+        assert_eq!(subjects.id_mapping.len(), 2);
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        let msg = parse_error(r#"<subjects version="2"><id_table/></subjects>"#);
+        assert!(msg.starts_with("test.xml:1: <subjects>"), "{}", msg);
+        assert!(msg.contains("unsupported schema version '2' (expected '1')"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_other_root_elements() {
+        let msg = parse_error("<pegs/>");
+        assert!(msg.starts_with("test.xml:1: <pegs>"), "{}", msg);
+        assert!(msg.ends_with("expected <subjects> as the root element"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_unexpected_elements_by_name_and_line() {
+        let msg = parse_error("<subjects>\n<id_table>\n<entry id=\"0\" peg=\"(unit)\"/>\n</id_table>\n</subjects>");
+        assert!(msg.starts_with("test.xml:3: <entry>"), "{}", msg);
+        assert!(msg.contains("unexpected element in <id_table>"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_missing_attributes() {
+        let msg = parse_error(r#"<subjects><subject method="f"><pid>0</pid></subject><id_table/></subjects>"#);
+        assert!(msg.contains("missing attribute 'sourcefile'"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_subjects_without_a_pid() {
+        let msg = parse_error(r#"<subjects><subject sourcefile="A.java" method="f"></subject><id_table/></subjects>"#);
+        assert!(msg.contains("<subject>") && msg.contains("missing <pid> element"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_non_numeric_mutant_ids() {
+        let msg = parse_error(r#"<subjects><subject sourcefile="A.java" method="f"><pid>0</pid><mutant mid="x" pid="0"/></subject><id_table/></subjects>"#);
+        assert!(msg.contains("mutant id 'x' is not a number"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_a_missing_id_table() {
+        let msg = parse_error("<subjects></subjects>");
+        assert_eq!(msg, "test.xml: missing <id_table> element");
+    }
+
+    #[test]
+    fn rejects_malformed_xml() {
+        let msg = parse_error("<subjects><id_table></subjects>");
+        assert!(msg.contains("malformed XML"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_ids_out_of_order() {
+        let err = load(r#"<subjects><id_table><dedup_entry id="1" peg="(unit)"/><dedup_entry id="1" peg="(null)"/></id_table></subjects>"#, false)
+            .err()
+            .unwrap();
+        match err {
+            DetectorError::NonMonotonicId { id: 1, previous: 1, .. } => (),
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn rejects_dangling_children() {
+        let err = load(r#"<subjects><id_table><dedup_entry id="0" peg="1"/><dedup_entry id="1" peg="(+ 0 2)"/></id_table></subjects>"#, false)
+            .err()
+            .unwrap();
+        match err {
+            DetectorError::DanglingChildId { id: 1, child: 2, .. } => (),
+            e => panic!("unexpected error {}", e),
+        }
+    }
 
+    #[test]
+    fn rejects_unknown_operators() {
+        let err = load(r#"<subjects><id_table><dedup_entry id="0" peg="1"/><dedup_entry id="1" peg="(+ 0 0 0)"/></id_table></subjects>"#, false)
+            .err()
+            .unwrap();
+        match err {
+            DetectorError::UnknownOperator { id: 1, ref peg, .. } => assert_eq!(peg, "(+ 0 0 0)"),
+            e => panic!("unexpected error {}", e),
+        }
+    }
 
+    #[test]
+    fn rejects_equivalences_of_undefined_ids() {
+        let msg = parse_error(r#"<subjects><id_table><dedup_entry id="0" peg="1"/></id_table><node_equivalences><node_equivalence><first>0</first><second>9</second></node_equivalence></node_equivalences></subjects>"#);
+        assert!(msg.contains("node equivalence refers to id '9', which is not in the id table"), "{}", msg);
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// Where in a subject file an error was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    /// 1-based line number, when the error can be tied to an element
+    pub line: Option<u64>,
+}
+
+impl Location {
+    pub fn file(file: &str) -> Self {
+        Location { file: file.to_string(), line: None }
+    }
+
+    pub fn line(file: &str, line: u64) -> Self {
+        Location { file: file.to_string(), line: Some(line) }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file, line),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DetectorError {
+    /// Malformed XML, or an element that doesn't fit the subjects schema
+    Parse { at: Location, msg: String },
+    /// An id table entry whose peg refers to an id that hasn't been defined
+    DanglingChildId { at: Location, id: usize, child: usize },
+    /// An id table entry whose operator (or its arity) isn't part of the `Peg`
+    /// language
+    UnknownOperator { at: Location, id: usize, peg: String, reason: String },
+    /// A subject (`mid` is `None`) or one of its mutants whose pid isn't in
+    /// the id table
+    MissingSubjectPid { at: Location, method: String, mid: Option<u32>, pid: String },
+    /// An id table entry whose id doesn't exceed the id before it
+    NonMonotonicId { at: Location, id: usize, previous: usize },
+    Io { at: Location, error: std::io::Error },
+}
+
+impl DetectorError {
+    pub fn location(&self) -> &Location {
+        match self {
+            DetectorError::Parse { at, .. }
+            | DetectorError::DanglingChildId { at, .. }
+            | DetectorError::UnknownOperator { at, .. }
+            | DetectorError::MissingSubjectPid { at, .. }
+            | DetectorError::NonMonotonicId { at, .. }
+            | DetectorError::Io { at, .. } => at,
+        }
+    }
+}
+
+impl fmt::Display for DetectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectorError::Parse { at, msg } => write!(f, "{}: {}", at, msg),
+            DetectorError::DanglingChildId { at, id, child } => {
+                write!(f, "{}: id {} refers to undefined child id {}", at, id, child)
+            }
+            DetectorError::UnknownOperator { at, id, peg, reason } => {
+                write!(f, "{}: id {} has unrecognized peg '{}': {}", at, id, peg, reason)
+            }
+            DetectorError::MissingSubjectPid { at, method, mid: None, pid } => {
+                write!(f, "{}: pid '{}' of subject {} is not in the id table", at, pid, method)
+            }
+            DetectorError::MissingSubjectPid { at, method, mid: Some(mid), pid } => {
+                write!(f, "{}: pid '{}' of mutant {} in subject {} is not in the id table", at, pid, mid, method)
+            }
+            DetectorError::NonMonotonicId { at, id, previous } => {
+                write!(f, "{}: id {} <= previous id {}", at, id, previous)
+            }
+            DetectorError::Io { at, error } => write!(f, "{}: {}", at, error),
+        }
+    }
+}
+
+impl std::error::Error for DetectorError {}
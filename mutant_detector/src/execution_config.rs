@@ -1,5 +1,11 @@
 use crate::app_config::AppConfig;
+use crate::mutants_log::MutantsLog;
+use crate::purity::PurityTable;
+use crate::results::OutputFormat;
+use crate::rewrites::RULE_GROUPS;
+use crate::rule_file::RuleSpec;
 use instant::Duration;
+use std::sync::Arc;
 
 pub struct ExecutionConfig {
     pub max_iterations: usize,
@@ -7,6 +13,19 @@ pub struct ExecutionConfig {
     pub execution_timeout: Duration,
     pub halt_on_error: bool,
     pub results_directory: String,
+    pub isolate_subjects: bool,
+    pub jobs: usize,
+    pub exclude_unsound_rules: bool,
+    /// The groups of built-in rules to use, in `RULE_GROUPS` order
+    pub rule_groups: Vec<String>,
+    pub purity: Arc<PurityTable>,
+    /// The file `user_rules` were read from
+    pub rules_file: Option<String>,
+    pub user_rules: Arc<Vec<RuleSpec>>,
+    pub replace_builtin_rules: bool,
+    pub explain: bool,
+    pub format: OutputFormat,
+    pub mutants_log: Option<Arc<MutantsLog>>,
 }
 
 impl Default for ExecutionConfig {
@@ -17,6 +36,17 @@ impl Default for ExecutionConfig {
             execution_timeout: Duration::from_secs(5),
             halt_on_error: false,
             results_directory: "equivalence_results".to_string(), // Updated default output directory
+            isolate_subjects: false,
+            jobs: 1,
+            exclude_unsound_rules: false,
+            rule_groups: RULE_GROUPS.iter().map(|group| group.to_string()).collect(),
+            purity: Arc::new(PurityTable::builtin()),
+            rules_file: None,
+            user_rules: Arc::new(vec![]),
+            replace_builtin_rules: false,
+            explain: false,
+            format: OutputFormat::EquivClass,
+            mutants_log: None,
         }
     }
 }
@@ -46,6 +76,67 @@ impl ExecutionConfig {
         self.results_directory = results_directory;
         self
     }
+
+    pub fn set_isolate_subjects(mut self, isolate_subjects: bool) -> Self {
+        self.isolate_subjects = isolate_subjects;
+        self
+    }
+
+    pub fn set_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = if jobs == 0 {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            jobs
+        };
+        self
+    }
+
+    pub fn set_exclude_unsound_rules(mut self, exclude_unsound_rules: bool) -> Self {
+        self.exclude_unsound_rules = exclude_unsound_rules;
+        self
+    }
+
+    /// Use the groups in `enabled` (all of them, if `None`) that aren't in
+    /// `disabled`
+    pub fn set_rule_groups(mut self, enabled: Option<&[String]>, disabled: &[String]) -> Self {
+        self.rule_groups = RULE_GROUPS.iter()
+            .filter(|group| enabled.map_or(true, |enabled| enabled.iter().any(|g| g == *group)))
+            .filter(|group| !disabled.iter().any(|g| g == *group))
+            .map(|group| group.to_string())
+            .collect();
+        self
+    }
+
+    pub fn set_purity_table(mut self, purity: PurityTable) -> Self {
+        self.purity = Arc::new(purity);
+        self
+    }
+
+    pub fn set_user_rules(mut self, rules_file: String, user_rules: Vec<RuleSpec>) -> Self {
+        self.rules_file = Some(rules_file);
+        self.user_rules = Arc::new(user_rules);
+        self
+    }
+
+    pub fn set_replace_builtin_rules(mut self, replace_builtin_rules: bool) -> Self {
+        self.replace_builtin_rules = replace_builtin_rules;
+        self
+    }
+
+    pub fn set_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    pub fn set_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn set_mutants_log(mut self, mutants_log: MutantsLog) -> Self {
+        self.mutants_log = Some(Arc::new(mutants_log));
+        self
+    }
 }
 
 impl ToString for ExecutionConfig {
@@ -55,12 +146,32 @@ impl ToString for ExecutionConfig {
 Node Limit: {}
 Timeout: {}
 Halt on Error: {}
-Results Directory: {}",
+Results Directory: {}
+Isolate Subjects: {}
+Jobs: {}
+Exclude Unsound Rules: {}
+Rule Groups: {}
+Purity Table: {}
+Rules File: {}
+Replace Built-in Rules: {}
+Explain: {}
+Output Format: {}
+Mutants Log: {}",
             self.max_iterations,
             self.max_nodes,
             self.execution_timeout.as_secs(),
             self.halt_on_error,
-            self.results_directory
+            self.results_directory,
+            self.isolate_subjects,
+            self.jobs,
+            self.exclude_unsound_rules,
+            self.rule_groups.join(","),
+            self.purity.source,
+            self.rules_file.as_deref().unwrap_or("none"),
+            self.replace_builtin_rules,
+            self.explain,
+            self.format,
+            self.mutants_log.as_ref().map_or("none", |log| log.file.as_str())
         )
     }
 }
@@ -73,5 +184,12 @@ impl From<AppConfig> for ExecutionConfig {
             .set_execution_timeout(Duration::from_secs(args.execution_timeout))
             .set_halt_on_error(args.halt_on_error)
             .set_results_directory(args.results_directory)
+            .set_isolate_subjects(args.isolate_subjects)
+            .set_jobs(args.jobs)
+            .set_exclude_unsound_rules(args.exclude_unsound_rules)
+            .set_replace_builtin_rules(args.replace_builtin_rules)
+            .set_rule_groups(args.enable_rules.as_deref(), &args.disable_rules)
+            .set_explain(args.explain)
+            .set_format(args.format)
     }
 }
\ No newline at end of file
@@ -0,0 +1,445 @@
+use crate::driver::Subject;
+use crate::peg::{EGraph, Peg, PegAnalysis};
+use crate::rewrites::{Rule, RuleCondition, RewriteSystem};
+use crate::subsumption::MutationPoint;
+use crate::verify::{instantiate, pattern_vars};
+use egg::{Applier, Id, Language, Pattern, Rewrite, Subst, Var};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// How deep terms are printed before eliding subterms. PEGs of loops are
+/// cyclic
+const MAX_TERM_DEPTH: usize = 4;
+
+/// How many levels of congruence `Proofs::render` explains by the merges of
+/// the children
+const MAX_NESTING: usize = 8;
+
+/// The justification of merges made without a rule: rebuilding merges the
+/// parents of merged classes, and the analysis merges classes with the
+/// constants they fold to
+const CONGRUENCE: &str = "congruence or constant folding";
+
+/// The nodes of each class of an e-graph, before saturation
+pub type Snapshot = HashMap<Id, Vec<Peg>>;
+
+pub fn snapshot(egraph: &EGraph) -> Snapshot {
+    egraph.classes()
+        .map(|class| {
+            let nodes = class.nodes.iter()
+                .map(|node| {
+                    let mut node = node.clone();
+                    node.for_each_mut(|child| *child = egraph.find(*child));
+                    node
+                })
+                .collect();
+            (egraph.find(class.id), nodes)
+        })
+        .collect()
+}
+
+/// Like `peg::to_sexp_string`, for class `id` of an e-graph whose first nodes
+/// are given by `first_node`, with subterms deeper than `depth` elided as `...`
+fn print_term(first_node: &dyn Fn(Id) -> Peg, id: Id, depth: usize) -> String {
+    let node = first_node(id);
+    if node.is_leaf() {
+        node.display_op().to_string()
+    } else if depth == 0 {
+        "...".to_string()
+    } else {
+        let mut parts = vec![node.display_op().to_string()];
+        node.for_each(|child| parts.push(print_term(first_node, child, depth - 1)));
+        format!("({})", parts.join(" "))
+    }
+}
+
+/// A merge made while saturating: `rule` equated the term `lhs` of class
+/// `from` with the term `rhs` of class `to`
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub rule: String,
+    pub from: Id,
+    pub to: Id,
+    pub lhs: String,
+    pub rhs: String,
+}
+
+/// The merges the rewrites of `recording_rewrites` make, in order
+#[derive(Clone, Debug, Default)]
+pub struct ProofLog(Arc<Mutex<Vec<Step>>>);
+
+impl ProofLog {
+    fn record(&self, step: Step) {
+        self.0.lock().unwrap().push(step);
+    }
+
+    fn take(&self) -> Vec<Step> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Applies a rule's right hand side like the rule's own rewrite does, logging
+/// each application that merges two classes
+struct RecordingApplier {
+    name: String,
+    lhs: String,
+    rhs: Pattern<Peg>,
+    rhs_text: String,
+    condition: Option<RuleCondition>,
+    log: ProofLog,
+}
+
+impl Applier<Peg, PegAnalysis> for RecordingApplier {
+    fn apply_one(&self, egraph: &mut EGraph, eclass: Id, subst: &Subst) -> Vec<Id> {
+        if let Some(condition) = &self.condition {
+            if !condition(egraph, eclass, subst) {
+                return vec![];
+            }
+        }
+        let ids = self.rhs.apply_one(egraph, eclass, subst);
+        for &id in &ids {
+            if egraph.find(id) != egraph.find(eclass) {
+                let first_node = |id: Id| egraph[id].nodes[0].clone();
+                let bindings: Vec<(String, String)> = pattern_vars(&self.lhs).into_iter()
+                    .map(|var| {
+                        let id = subst[var.parse::<Var>().unwrap()];
+                        (var, print_term(&first_node, id, MAX_TERM_DEPTH - 1))
+                    })
+                    .collect();
+                self.log.record(Step {
+                    rule: self.name.clone(),
+                    from: eclass,
+                    to: id,
+                    lhs: instantiate(&self.lhs, &bindings),
+                    rhs: instantiate(&self.rhs_text, &bindings),
+                });
+            }
+        }
+        ids
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        self.rhs.vars()
+    }
+}
+
+/// Rewrites equivalent to `rules`, that log the merges they make to `log`
+pub fn recording_rewrites(rules: Vec<Rule>, log: &ProofLog) -> Box<RewriteSystem> {
+    rules.into_iter()
+        .map(|rule| {
+            let name = rule.name().to_string();
+            // The rules' patterns parsed when they were built
+            let lhs: Pattern<Peg> = rule.lhs.parse().unwrap();
+            let applier = RecordingApplier {
+                name: name.clone(),
+                rhs: rule.rhs.parse().unwrap(),
+                lhs: rule.lhs,
+                rhs_text: rule.rhs,
+                condition: rule.condition,
+                log: log.clone(),
+            };
+            Rewrite::new(name.clone(), name, lhs, applier).unwrap()
+        })
+        .collect()
+}
+
+/// The merges made while saturating an e-graph, as a graph whose edges are
+/// steps between the ids they merged
+pub struct Proofs {
+    initial: Snapshot,
+    steps: Vec<Step>,
+    /// The steps at each id, by index into `steps`
+    adjacent: HashMap<Id, Vec<usize>>,
+    /// The class of each id after saturation
+    classes: HashMap<Id, Id>,
+}
+
+impl Proofs {
+    /// The proofs of the merges in `log`, made while saturating the e-graph
+    /// of `initial` into `egraph`. Ids that ended up in the same class without
+    /// a chain of rules between them are linked by `CONGRUENCE` steps,
+    /// preferably between nodes that agree but for merged children
+    pub fn new(log: &ProofLog, initial: Snapshot, egraph: &EGraph) -> Self {
+        let steps = log.take();
+        let classes = initial.keys()
+            .chain(steps.iter().flat_map(|step| vec![&step.from, &step.to]))
+            .map(|&id| (id, egraph.find(id)))
+            .collect();
+        let mut proofs = Proofs { initial, steps, adjacent: HashMap::new(), classes };
+
+        let mut components = HashMap::new();
+        for step in &proofs.steps {
+            union(&mut components, step.from, step.to);
+        }
+        let mut by_class: HashMap<Id, Vec<Id>> = HashMap::new();
+        for (&id, &class) in &proofs.classes {
+            by_class.entry(class).or_default().push(id);
+        }
+        let mut links = vec![];
+        for ids in by_class.values_mut().filter(|ids| ids.len() > 1) {
+            ids.sort();
+            for (i, &b) in ids.iter().enumerate() {
+                for &a in &ids[..i] {
+                    if proofs.differing_children(a, b).is_some() && union(&mut components, a, b) {
+                        links.push((a, b));
+                    }
+                }
+            }
+            for &b in &ids[1..] {
+                if union(&mut components, ids[0], b) {
+                    links.push((ids[0], b));
+                }
+            }
+        }
+        for (from, to) in links {
+            let (lhs, rhs) = (proofs.term(from), proofs.term(to));
+            proofs.steps.push(Step { rule: CONGRUENCE.to_string(), from, to, lhs, rhs });
+        }
+
+        for (index, step) in proofs.steps.iter().enumerate() {
+            proofs.adjacent.entry(step.from).or_default().push(index);
+            proofs.adjacent.entry(step.to).or_default().push(index);
+        }
+        proofs
+    }
+
+    /// The term of `id` before saturation, or as the first rule that merged it
+    /// printed it
+    fn term(&self, id: Id) -> String {
+        if self.initial.contains_key(&id) {
+            print_term(&|id| self.initial[&id][0].clone(), id, MAX_TERM_DEPTH)
+        } else {
+            self.steps.iter()
+                .find_map(|step| {
+                    if step.from == id {
+                        Some(step.lhs.clone())
+                    } else if step.to == id {
+                        Some(step.rhs.clone())
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_else(|| "?".to_string())
+        }
+    }
+
+    /// Whether `a` and `b` ended up in the same class
+    fn merged(&self, a: Id, b: Id) -> bool {
+        self.classes.contains_key(&a) && self.classes.get(&a) == self.classes.get(&b)
+    }
+
+    /// The pairs of children that differ between a node of `a` and a node of
+    /// `b` (before saturation) with the same operator, whose children all
+    /// ended up merged
+    fn differing_children(&self, a: Id, b: Id) -> Option<Vec<(Id, Id)>> {
+        let (a_nodes, b_nodes) = (self.initial.get(&a)?, self.initial.get(&b)?);
+        a_nodes.iter().find_map(|a_node| b_nodes.iter().find_map(|b_node| {
+            let pairs = a_node.children().iter().copied().zip(b_node.children().iter().copied());
+            if a_node.matches(b_node) && pairs.clone().all(|(c, d)| self.merged(c, d)) {
+                Some(pairs.filter(|(c, d)| c != d).collect())
+            } else {
+                None
+            }
+        }))
+    }
+
+    /// The shortest chain of steps from `from` to `to`, each with whether it is
+    /// taken from its left hand side to its right. Chains of rules are
+    /// preferred to those that take `CONGRUENCE` steps
+    pub fn chain(&self, from: Id, to: Id) -> Option<Vec<(&Step, bool)>> {
+        self.search(from, to, false).or_else(|| self.search(from, to, true))
+    }
+
+    fn search(&self, from: Id, to: Id, congruence: bool) -> Option<Vec<(&Step, bool)>> {
+        let mut previous: HashMap<Id, (Id, usize)> = HashMap::new();
+        let mut seen: HashSet<Id> = std::iter::once(from).collect();
+        let mut queue: VecDeque<Id> = std::iter::once(from).collect();
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut chain = vec![];
+                let mut at = to;
+                while let Some(&(before, index)) = previous.get(&at) {
+                    let step = &self.steps[index];
+                    chain.push((step, step.from == before));
+                    at = before;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for &index in self.adjacent.get(&id).into_iter().flatten() {
+                let step = &self.steps[index];
+                if !congruence && step.rule == CONGRUENCE {
+                    continue;
+                }
+                let next = if step.from == id { step.to } else { step.from };
+                if seen.insert(next) {
+                    previous.insert(next, (id, index));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Add the lines of the chain from `from` to `to` to `lines`. A
+    /// `CONGRUENCE` step between nodes that agree but for merged children is
+    /// explained by the chains of those children, indented under it, unless
+    /// it is already being explained further up, as loops do
+    fn render(&self, from: Id, to: Id, nesting: usize, visiting: &mut HashSet<(Id, Id)>, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(nesting + 2);
+        let chain = match self.chain(from, to) {
+            Some(chain) => chain,
+            None => return lines.push(format!("{}(no recorded merges connect them)", indent)),
+        };
+        for (step, forwards) in chain {
+            let (a, b) = if forwards { (step.from, step.to) } else { (step.to, step.from) };
+            if step.rule == CONGRUENCE {
+                match self.differing_children(a, b) {
+                    Some(children) if nesting < MAX_NESTING && visiting.insert((a, b)) => {
+                        lines.push(format!("{}{} = {}  [congruence]", indent, self.term(a), self.term(b)));
+                        for (c, d) in children {
+                            self.render(c, d, nesting + 1, visiting, lines);
+                        }
+                        visiting.remove(&(a, b));
+                    }
+                    Some(_) => lines.push(format!("{}{} = {}  [congruence, around the loop]", indent, self.term(a), self.term(b))),
+                    None => lines.push(format!("{}{} = {}  [{}]", indent, self.term(a), self.term(b), CONGRUENCE)),
+                }
+            } else if forwards {
+                lines.push(format!("{}{} = {}  [{}]", indent, step.lhs, step.rhs, step.rule));
+            } else {
+                lines.push(format!("{}{} = {}  [{}, right to left]", indent, step.rhs, step.lhs, step.rule));
+            }
+        }
+    }
+}
+
+/// Merge the components of `a` and `b`, unless they are the same one
+fn union(components: &mut HashMap<Id, Id>, a: Id, b: Id) -> bool {
+    let (a, b) = (component(components, a), component(components, b));
+    if a != b {
+        components.insert(a, b);
+    }
+    a != b
+}
+
+fn component(components: &mut HashMap<Id, Id>, id: Id) -> Id {
+    match components.get(&id).copied() {
+        Some(parent) if parent != id => {
+            let root = component(components, parent);
+            components.insert(id, root);
+            root
+        }
+        _ => id,
+    }
+}
+
+fn root(pid: &str, raw_id_to_egg_id: &HashMap<Id, Id>) -> Option<Id> {
+    pid.parse::<usize>().ok().and_then(|raw_id| raw_id_to_egg_id.get(&Id::from(raw_id)).copied())
+}
+
+/// Describe why the mutants of `subj` that share an equivalence class were
+/// merged: for each mutant but the first of its class, the chain of rewrites
+/// from the term the first one has at the mutation point to the term it has.
+/// When the two don't change the same term, or those terms weren't merged, the
+/// chain goes from the first one's method to the mutant's
+pub fn explain(
+    subj: &Subject,
+    points: &HashMap<u32, MutationPoint>,
+    raw_id_to_egg_id: &HashMap<Id, Id>,
+    proofs: &Proofs,
+) -> String {
+    let pid = |mid: u32| {
+        if mid == 0 {
+            Some(subj.pid.as_str())
+        } else {
+            subj.mutants.iter().find(|mutant| mutant.mid == mid).map(|mutant| mutant.pid.as_str())
+        }
+    };
+
+    let mut lines = vec![format!("subject {}", subj.method)];
+    for class in &subj.analysis_result.equivalence_classes {
+        let mut mids: Vec<u32> = class.iter().copied().collect();
+        mids.sort();
+        let first = mids[0];
+        for &mid in &mids[1..] {
+            let merged_with = if first == 0 { "the original".to_string() } else { format!("mutant {}", first) };
+            lines.push(format!("  mutant {} merged with {}", mid, merged_with));
+
+            let at_points = points.get(&mid)
+                .and_then(|point| match first {
+                    0 => Some((point.original, point.mutant, !point.path.is_empty())),
+                    _ => points.get(&first)
+                        .filter(|other| other.path == point.path && other.original == point.original)
+                        .map(|other| (other.mutant, point.mutant, !point.path.is_empty())),
+                })
+                .filter(|&(a, b, _)| proofs.merged(a, b));
+            let at_roots = || Some((root(pid(first)?, raw_id_to_egg_id)?, root(pid(mid)?, raw_id_to_egg_id)?, false));
+            match at_points.or_else(at_roots) {
+                Some((a, b, around)) => {
+                    proofs.render(a, b, 0, &mut HashSet::new(), &mut lines);
+                    if around {
+                        lines.push("    and the methods agree around the mutation point, so they merge by congruence".to_string());
+                    }
+                }
+                None => lines.push("    (no recorded merges connect them)".to_string()),
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_config::ExecutionConfig;
+    use crate::rewrites::rules_for;
+
+    /// Explain the merges of `mutant` with `original`, saturating with the
+    /// built-in rules named `rules`
+    fn explain_mutant(original: &str, mutant: &str, rules: &[&str]) -> String {
+        let mut egraph = EGraph::default();
+        let original = egraph.add_expr(&original.parse().unwrap());
+        let mutant = egraph.add_expr(&mutant.parse().unwrap());
+        let raw_id_to_egg_id: HashMap<Id, Id> = vec![(Id::from(1), original), (Id::from(2), mutant)].into_iter().collect();
+        let mut subj = Subject::new("Test.java".to_string(), "Test@m(int,int)".to_string(), &[(0, "1"), (1, "2")]);
+        let points = crate::subsumption::mutation_points(&subj, &egraph, &raw_id_to_egg_id);
+
+        let log = ProofLog::default();
+        let rules = rules_for(&ExecutionConfig::default()).into_iter().filter(|rule| rules.contains(&rule.name())).collect();
+        let rules = recording_rewrites(rules, &log);
+        let initial = snapshot(&egraph);
+        let runner = egg::Runner::default().with_egraph(egraph).with_iter_limit(10).run(&*rules);
+        assert_eq!(runner.egraph.find(original), runner.egraph.find(mutant));
+
+        subj.analysis_result.equivalence_classes = vec![vec![0, 1].into_iter().collect()];
+        explain(&subj, &points, &raw_id_to_egg_id, &Proofs::new(&log, initial, &runner.egraph))
+    }
+
+    #[test]
+    fn explains_a_merge_at_the_mutation_point_by_the_rules_applied() {
+        let explanation = explain_mutant(
+            "(+ (* (var a \"int\") 1) 2)",
+            "(+ (var a \"int\") 2)",
+            &["mul-ident"],
+        );
+        assert_eq!(explanation, "subject Test@m(int,int)\n\
+            \x20 mutant 1 merged with the original\n\
+            \x20   (* (var a \"int\") 1) = (var a \"int\")  [mul-ident]\n\
+            \x20   and the methods agree around the mutation point, so they merge by congruence");
+    }
+
+    #[test]
+    fn explains_congruence_by_the_merges_of_the_children() {
+        let explanation = explain_mutant(
+            "(- (* (var a \"int\") 1) (var b \"int\"))",
+            "(- (var a \"int\") (* (var b \"int\") 1))",
+            &["mul-ident"],
+        );
+        assert_eq!(explanation, "subject Test@m(int,int)\n\
+            \x20 mutant 1 merged with the original\n\
+            \x20   (- (* (var a \"int\") 1) (var b \"int\")) = (- (var a \"int\") (* (var b \"int\") 1))  [congruence]\n\
+            \x20     (* (var a \"int\") 1) = (var a \"int\")  [mul-ident]\n\
+            \x20     (var b \"int\") = (* (var b \"int\") 1)  [mul-ident, right to left]");
+    }
+}
@@ -0,0 +1,251 @@
+use crate::peg::Peg;
+use crate::primitives::NumericType;
+
+/// The values an `int` (or, if `is_long`, a `long`) expression may take.
+///
+/// Arithmetic on intervals is exact in i128, and gives `None` whenever the
+/// result may leave the range of its type: an interval only exists for
+/// expressions that are known not to overflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: i64,
+    pub hi: i64,
+    pub is_long: bool,
+}
+
+impl Interval {
+    fn new(lo: i128, hi: i128, is_long: bool) -> Option<Interval> {
+        let (min, max) = if is_long {
+            (i64::MIN as i128, i64::MAX as i128)
+        } else {
+            (i32::MIN as i128, i32::MAX as i128)
+        };
+        if min <= lo && lo <= hi && hi <= max {
+            Some(Interval { lo: lo as i64, hi: hi as i64, is_long })
+        } else {
+            None
+        }
+    }
+
+    /// The interval holding exactly the value of an integral constant. Narrow
+    /// types are promoted to `int`, as they are by every operator
+    pub fn of_constant(constant: &Peg) -> Option<Interval> {
+        match constant {
+            Peg::Long(n) => Interval::new(n.value() as i128, n.value() as i128, true),
+            Peg::Num(_) | Peg::Short(_) | Peg::Byte(_) | Peg::Char(_) => {
+                let n = constant.as_number()?.to_java_int().value() as i128;
+                Interval::new(n, n, false)
+            }
+            _ => None,
+        }
+    }
+
+    /// The interval holding every value of an integral type, promoted as by
+    /// `of_constant`
+    pub fn of_type(ty: NumericType) -> Option<Interval> {
+        match ty {
+            NumericType::Byte => Interval::new(i8::MIN as i128, i8::MAX as i128, false),
+            NumericType::Short => Interval::new(i16::MIN as i128, i16::MAX as i128, false),
+            NumericType::Char => Interval::new(0, u16::MAX as i128, false),
+            NumericType::Int => Interval::new(i32::MIN as i128, i32::MAX as i128, false),
+            NumericType::Long => Interval::new(i64::MIN as i128, i64::MAX as i128, true),
+            NumericType::Float | NumericType::Double => None,
+        }
+    }
+
+    /// The values of a cast to the integral type `to` of an expression with
+    /// values `a`, which are kept when they fit in `to`. Casts from floating
+    /// point types and narrowing casts that wrap may give any value of `to`
+    pub fn convert(a: Option<Interval>, to: NumericType) -> Option<Interval> {
+        let bounds = Interval::of_type(to)?;
+        match a {
+            Some(a) if bounds.lo <= a.lo && a.hi <= bounds.hi => {
+                Interval::new(a.lo as i128, a.hi as i128, bounds.is_long)
+            }
+            _ => Some(bounds),
+        }
+    }
+
+    pub fn contains_zero(&self) -> bool {
+        self.lo <= 0 && 0 <= self.hi
+    }
+
+    pub fn is_nonneg(&self) -> bool {
+        self.lo >= 0
+    }
+
+    fn bounds(&self) -> (i128, i128) {
+        (self.lo as i128, self.hi as i128)
+    }
+
+    /// Evaluate the binary operator `op` (one of `+`, `-`, `*`, `/`, `%`) over
+    /// the intervals, with binary numeric promotion deciding the result type
+    pub fn binary(op: &str, a: Interval, b: Interval) -> Option<Interval> {
+        let is_long = a.is_long || b.is_long;
+        let (a_lo, a_hi) = a.bounds();
+        let (b_lo, b_hi) = b.bounds();
+        let corners = |f: fn(i128, i128) -> i128| {
+            let values = [f(a_lo, b_lo), f(a_lo, b_hi), f(a_hi, b_lo), f(a_hi, b_hi)];
+            let lo = *values.iter().min().unwrap();
+            let hi = *values.iter().max().unwrap();
+            Interval::new(lo, hi, is_long)
+        };
+        match op {
+            "+" => Interval::new(a_lo + b_lo, a_hi + b_hi, is_long),
+            "-" => Interval::new(a_lo - b_hi, a_hi - b_lo, is_long),
+            "*" => corners(|x, y| x * y),
+            // With the divisor's sign fixed, truncating division is monotonic
+            // in each operand, so the extremes are at the corners
+            "/" if !b.contains_zero() => corners(|x, y| x / y),
+            // The remainder takes the sign of the dividend, and is smaller in
+            // magnitude than the divisor
+            "%" if !b.contains_zero() => {
+                let m = b_lo.abs().max(b_hi.abs()) - 1;
+                Interval::new(
+                    if a_lo >= 0 { 0 } else { a_lo.max(-m) },
+                    if a_hi <= 0 { 0 } else { a_hi.min(m) },
+                    is_long,
+                )
+            }
+            _ => None,
+        }
+    }
+
+    pub fn neg(a: Interval) -> Option<Interval> {
+        let (lo, hi) = a.bounds();
+        Interval::new(-hi, -lo, a.is_long)
+    }
+
+    /// `a & b` is non-negative and at most the smaller of any non-negative
+    /// operands
+    pub fn bin_and(a: Interval, b: Interval) -> Option<Interval> {
+        let is_long = a.is_long || b.is_long;
+        match (a.is_nonneg(), b.is_nonneg()) {
+            (true, true) => Interval::new(0, a.hi.min(b.hi) as i128, is_long),
+            (true, false) => Interval::new(0, a.hi as i128, is_long),
+            (false, true) => Interval::new(0, b.hi as i128, is_long),
+            (false, false) => None,
+        }
+    }
+
+    /// A signed right shift moves its operand towards 0 (or -1), whatever the
+    /// distance
+    pub fn srshift(a: Interval) -> Option<Interval> {
+        let lo = if a.lo >= 0 { 0 } else { a.lo };
+        let hi = if a.hi < 0 { -1 } else { a.hi };
+        Interval::new(lo as i128, hi as i128, a.is_long)
+    }
+
+    pub fn urshift(a: Interval) -> Option<Interval> {
+        if a.is_nonneg() {
+            Interval::new(0, a.hi as i128, a.is_long)
+        } else {
+            None
+        }
+    }
+
+    /// The smallest interval holding both
+    pub fn hull(a: Interval, b: Interval) -> Option<Interval> {
+        if a.is_long != b.is_long {
+            return None;
+        }
+        Interval::new(a.lo.min(b.lo) as i128, a.hi.max(b.hi) as i128, a.is_long)
+    }
+
+    /// Both intervals describe the same value, so it lies in their
+    /// intersection. `None` when they disagree, which only unsound rewrites
+    /// can cause
+    pub fn intersect(a: Interval, b: Interval) -> Option<Interval> {
+        if a.is_long != b.is_long {
+            return None;
+        }
+        Interval::new(a.lo.max(b.lo) as i128, a.hi.min(b.hi) as i128, a.is_long)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{JavaChar, JavaInt, JavaLong, JavaShort};
+
+    fn int(lo: i64, hi: i64) -> Interval {
+        Interval { lo, hi, is_long: false }
+    }
+
+    fn long(lo: i64, hi: i64) -> Interval {
+        Interval { lo, hi, is_long: true }
+    }
+
+    const INT_MAX: i64 = i32::MAX as i64;
+    const INT_MIN: i64 = i32::MIN as i64;
+
+    #[test]
+    fn constants_are_promoted() {
+        assert_eq!(Interval::of_constant(&Peg::Num(JavaInt::from(-3))), Some(int(-3, -3)));
+        assert_eq!(Interval::of_constant(&Peg::Long(JavaLong::from(3))), Some(long(3, 3)));
+        assert_eq!(Interval::of_constant(&Peg::Short(JavaShort::from(-7))), Some(int(-7, -7)));
+        assert_eq!(Interval::of_constant(&Peg::Char("'a'".parse::<JavaChar>().unwrap())), Some(int(97, 97)));
+        assert_eq!(Interval::of_constant(&Peg::Bool(true)), None);
+    }
+
+    #[test]
+    fn arithmetic_gives_none_when_it_may_overflow() {
+        assert_eq!(Interval::binary("+", int(1, 2), int(10, 20)), Some(int(11, 22)));
+        assert_eq!(Interval::binary("+", int(0, INT_MAX), int(0, 1)), None);
+        assert_eq!(Interval::binary("+", int(0, INT_MAX), long(0, 1)), Some(long(0, INT_MAX + 1)));
+        assert_eq!(Interval::binary("-", int(0, 5), int(1, 2)), Some(int(-2, 4)));
+        assert_eq!(Interval::binary("-", int(INT_MIN, 0), int(1, 1)), None);
+        assert_eq!(Interval::binary("*", int(-3, 2), int(-5, 4)), Some(int(-12, 15)));
+        assert_eq!(Interval::binary("*", int(65536, 65536), int(32768, 32768)), None);
+        assert_eq!(Interval::binary("*", long(i64::MIN, i64::MIN), long(-1, -1)), None);
+    }
+
+    #[test]
+    fn division_needs_a_nonzero_divisor() {
+        assert_eq!(Interval::binary("/", int(-10, 7), int(2, 5)), Some(int(-5, 3)));
+        assert_eq!(Interval::binary("/", int(-10, 7), int(-1, 1)), None);
+        assert_eq!(Interval::binary("/", int(INT_MIN, 0), int(-1, -1)), None);
+        assert_eq!(Interval::binary("%", int(-10, 7), int(3, 3)), Some(int(-2, 2)));
+        assert_eq!(Interval::binary("%", int(0, 7), int(-100, -10)), Some(int(0, 7)));
+        assert_eq!(Interval::binary("%", int(-20, -1), int(10, 10)), Some(int(-9, 0)));
+        assert_eq!(Interval::binary("%", int(1, 1), int(0, 3)), None);
+        assert_eq!(Interval::binary("<<", int(1, 1), int(3, 3)), None);
+    }
+
+    #[test]
+    fn unary_and_bitwise_operators() {
+        assert_eq!(Interval::neg(int(-3, 5)), Some(int(-5, 3)));
+        assert_eq!(Interval::neg(int(INT_MIN, 0)), None);
+        assert_eq!(Interval::bin_and(int(0, 12), int(0, 5)), Some(int(0, 5)));
+        assert_eq!(Interval::bin_and(int(-4, 4), int(0, 9)), Some(int(0, 9)));
+        assert_eq!(Interval::bin_and(int(-4, 4), int(-1, 9)), None);
+        assert_eq!(Interval::srshift(int(-8, 8)), Some(int(-8, 8)));
+        assert_eq!(Interval::srshift(int(3, 8)), Some(int(0, 8)));
+        assert_eq!(Interval::srshift(int(-8, -3)), Some(int(-8, -1)));
+        assert_eq!(Interval::urshift(int(3, 8)), Some(int(0, 8)));
+        assert_eq!(Interval::urshift(int(-1, 8)), None);
+    }
+
+    #[test]
+    fn hull_and_intersection_need_the_same_type() {
+        assert_eq!(Interval::hull(int(0, 3), int(5, 9)), Some(int(0, 9)));
+        assert_eq!(Interval::hull(int(0, 3), long(5, 9)), None);
+        assert_eq!(Interval::intersect(int(0, 6), int(5, 9)), Some(int(5, 6)));
+        assert_eq!(Interval::intersect(int(0, 3), int(5, 9)), None);
+        assert_eq!(Interval::intersect(long(0, 3), int(0, 3)), None);
+    }
+
+    #[test]
+    fn types_and_casts() {
+        assert_eq!(Interval::of_type(NumericType::Byte), Some(int(-128, 127)));
+        assert_eq!(Interval::of_type(NumericType::Char), Some(int(0, 65535)));
+        assert_eq!(Interval::of_type(NumericType::Long), Some(long(i64::MIN, i64::MAX)));
+        assert_eq!(Interval::of_type(NumericType::Float), None);
+        assert_eq!(Interval::convert(Some(int(-3, 3)), NumericType::Long), Some(long(-3, 3)));
+        assert_eq!(Interval::convert(Some(long(-3, 3)), NumericType::Short), Some(int(-3, 3)));
+        assert_eq!(Interval::convert(Some(int(0, 300)), NumericType::Byte), Some(int(-128, 127)));
+        assert_eq!(Interval::convert(Some(int(-1, 1)), NumericType::Char), Some(int(0, 65535)));
+        assert_eq!(Interval::convert(None, NumericType::Int), Some(int(INT_MIN, INT_MAX)));
+        assert_eq!(Interval::convert(Some(int(0, 1)), NumericType::Double), None);
+    }
+}
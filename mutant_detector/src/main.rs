@@ -1,11 +1,22 @@
-use mutant_detector::app_config::AppConfig;
+use mutant_detector::app_config::{AppConfig, Command};
 use mutant_detector::execution_config::ExecutionConfig;
 use mutant_detector::runtime_metrics::RuntimeMetrics;
 use mutant_detector::driver::parse_and_run;
 use mutant_detector::driver::*;
 
+use mutant_detector::error::DetectorError;
+use mutant_detector::mutants_log::{Breakdown, MutantsLog, Tally};
+use mutant_detector::purity::PurityTable;
+use mutant_detector::results::{write_records, write_subjects_to_single_file, OutputFormat};
+use mutant_detector::rewrites::{check_rule_groups, rules_for};
+use mutant_detector::rule_file::{self, RuleSpec};
+use mutant_detector::verify::verify_rules;
+
+use std::collections::BTreeMap;
 use std::fs::{create_dir, remove_dir_all};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use structopt::StructOpt;
 use std::fs::File;
 use std::io::prelude::*;
@@ -17,14 +28,36 @@ fn main() -> Result<(), String> {
         eprintln!("Warning: Failed to initialize logger.");
     });
     let args = AppConfig::from_args();
-    let config = ExecutionConfig::from(args.clone());
+    check_rule_groups(args.enable_rules.as_deref().unwrap_or(&[]))?;
+    check_rule_groups(&args.disable_rules)?;
+    let mut config = ExecutionConfig::from(args.clone());
+    if let Some(file) = &args.purity_table {
+        config = config.set_purity_table(PurityTable::load(file)?);
+    }
+    if let Some(file) = &args.mutants_log {
+        if args.source_files.len() > 1 {
+            return Err("--mutants-log needs a single subject file, as mutant ids are only unique within one".to_string());
+        }
+        config = config.set_mutants_log(MutantsLog::load(file)?);
+    }
+    if let Some(file) = &args.rules {
+        config = config.set_user_rules(file.clone(), rule_file::load(file)?);
+    }
+    if let Some(Command::VerifyRules { samples, seed, user_rules_only }) = &args.command {
+        return verify_rewrite_rules(&config, *samples, *seed, *user_rules_only);
+    }
     let mut global_data = RuntimeMetrics::default();
     let output_directory = &config.results_directory;
     prepare_output_directory(output_directory)?;
+    write_execution_config(&config)?;
 
-    args.source_files.iter().enumerate().try_for_each(|(index, subj_file)| {
-        process_subject_file(index, subj_file, &args, &config, &mut global_data)
-    })?;
+    if config.jobs > 1 && !config.isolate_subjects {
+        process_in_parallel(&args.source_files, &config, &mut global_data)?;
+    } else {
+        args.source_files.iter().enumerate().try_for_each(|(index, subj_file)| {
+            process_subject_file(index, subj_file, &args, &config, &mut global_data)
+        })?;
+    }
 
     if args.source_files.len() >= 1 {
         print_summary(global_data);
@@ -33,6 +66,48 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// Print a line per rule (only the rule file's, with `user_rules_only`) with the
+/// counterexamples found for it, failing if any rule tagged sound has one
+fn verify_rewrite_rules(
+    config: &ExecutionConfig,
+    samples: usize,
+    seed: u64,
+    user_rules_only: bool,
+) -> Result<(), String> {
+    let rules = if user_rules_only {
+        if config.rules_file.is_none() {
+            return Err("--user-rules-only needs a rule file (--rules)".to_string());
+        }
+        config.user_rules.iter().map(RuleSpec::to_rule).collect()
+    } else {
+        rules_for(config)
+    };
+    let reports = verify_rules(&rules, samples, seed);
+    for report in &reports {
+        println!(
+            "{} [{}]: {} checked, {} inconclusive, {} counterexamples",
+            report.name,
+            report.soundness.to_string(),
+            report.checked,
+            report.inconclusive,
+            report.counterexamples.len()
+        );
+        for counterexample in report.counterexamples.iter().take(3) {
+            println!("    {}", counterexample);
+        }
+    }
+
+    let mistagged: Vec<&str> = reports.iter()
+        .filter(|report| report.is_mistagged())
+        .map(|report| report.name.as_str())
+        .collect();
+    if mistagged.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Rules tagged sound have counterexamples: {}", mistagged.join(", ")))
+    }
+}
+
 fn prepare_output_directory(output_directory: &str) -> Result<(), String> {
     if Path::new(output_directory).exists() {
         remove_dir_all(output_directory).map_err(|e| e.to_string())?;
@@ -40,6 +115,14 @@ fn prepare_output_directory(output_directory: &str) -> Result<(), String> {
     create_dir(output_directory).map_err(|e| e.to_string())
 }
 
+/// Record the configuration alongside the results, so that a run can be
+/// reproduced
+fn write_execution_config(config: &ExecutionConfig) -> Result<(), String> {
+    let file_name = format!("{}/execution-config.txt", config.results_directory);
+    std::fs::write(&file_name, format!("{}\n", config.to_string()))
+        .map_err(|e| format!("Failed to write '{}': {}", file_name, e))
+}
+
 fn process_subject_file(
     index: usize,
     subj_file: &str,
@@ -47,19 +130,106 @@ fn process_subject_file(
     config: &ExecutionConfig,
     global_data: &mut RuntimeMetrics,
 ) -> Result<(), String> {
-    
-    match parse_and_run(subj_file, config, global_data) {
+    let result = parse_and_run(subj_file, config, global_data);
+    report_results(subj_file, result, config, global_data)
+}
+
+/// Analyze subject files on `config.jobs` worker threads. Results are reported
+/// and written in the order the files were given, so the output doesn't depend
+/// on how the files were scheduled.
+fn process_in_parallel(
+    source_files: &[String],
+    config: &ExecutionConfig,
+    global_data: &mut RuntimeMetrics,
+) -> Result<(), String> {
+    let next_file = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..config.jobs.min(source_files.len()) {
+            let sender = sender.clone();
+            let next_file = &next_file;
+            scope.spawn(move || loop {
+                let index = next_file.fetch_add(1, Ordering::SeqCst);
+                let subj_file = match source_files.get(index) {
+                    Some(subj_file) => subj_file,
+                    None => break,
+                };
+                let mut file_data = RuntimeMetrics::default();
+                let result = parse_and_run(subj_file, config, &mut file_data);
+                if sender.send((index, result, file_data)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next_to_report = 0;
+        for (index, result, file_data) in receiver {
+            pending.insert(index, (result, file_data));
+            while let Some((result, file_data)) = pending.remove(&next_to_report) {
+                global_data.merge(file_data);
+                report_results(&source_files[next_to_report], result, config, global_data)?;
+                next_to_report += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn report_results(
+    subj_file: &str,
+    result: Result<Subjects, DetectorError>,
+    config: &ExecutionConfig,
+    global_data: &mut RuntimeMetrics,
+) -> Result<(), String> {
+    match result {
         Ok(subjects) => {
+            for (subj, e) in &subjects.skipped {
+                eprintln!("Skipping subject {} in '{}': {}", subj.method, subj_file, e);
+            }
+            if config.halt_on_error && !subjects.skipped.is_empty() {
+                std::process::exit(1);
+            }
+
             let found = subjects.subjects.iter().map(|subj| subj.analysis_result.score).sum::<u32>();
             global_data.record_discovered_equivalences(found);
+            let equivalent = subjects.subjects.iter()
+                .map(|subj| subj.analysis_result.equivalent_to_original.len() as u32)
+                .sum::<u32>();
+            let duplicates = found - equivalent;
+            global_data.record_equivalent_and_duplicates(equivalent, duplicates);
             if found > 0 {
-                println!("    [+] Found {} equivalences", found);
+                println!("    [+] Found {} equivalent and {} duplicate mutants", equivalent, duplicates);
+            }
+            if let Some(log) = &config.mutants_log {
+                for subj in &subjects.subjects {
+                    let result = &subj.analysis_result;
+                    let merged = result.merged_mutants();
+                    for mutant in &subj.mutants {
+                        if let Some(row) = log.get(mutant.mid) {
+                            let equivalent = result.equivalent_to_original.contains(&mutant.mid);
+                            let duplicate = !equivalent && merged.contains(&mutant.mid);
+                            global_data.breakdown.record(row, equivalent, duplicate);
+                            if equivalent || duplicate {
+                                let kind = if equivalent { "equivalent" } else { "duplicate" };
+                                println!("        {} {} line {} ({}): {}", row.mid, row.operator, row.line, kind, row.transformation);
+                            }
+                        }
+                    }
+                }
             }
 
-            write_results(&subjects, subj_file, &config.results_directory)?
+            let dominators = subjects.subjects.iter()
+                .map(|subj| subj.analysis_result.subsumption.dominators.len() as u32)
+                .sum::<u32>();
+            global_data.record_dominators(dominators);
+
+            write_results(&subjects, subj_file, config)?
         }
-        Err(msg) => {
-            eprintln!("Error processing subject file '{}': {}", subj_file, msg);
+        Err(e) => {
+            eprintln!("Error processing subject file '{}': {}", subj_file, e);
             if config.halt_on_error {
                 std::process::exit(1);
             }
@@ -68,44 +238,86 @@ fn process_subject_file(
     Ok(())
 }
 
-fn write_results(subjects: &Subjects, subj_file: &str, output_directory: &str) -> Result<(), String> {
+fn write_results(subjects: &Subjects, subj_file: &str, config: &ExecutionConfig) -> Result<(), String> {
+    let output_directory = &config.results_directory;
     let file_name = format!("{}/{}.equiv-class", output_directory, subj_file.split('/').last().unwrap());
     write_subjects_to_single_file(subjects, &file_name)
-        .map_err(|e| format!("Failed to write results to '{}': {}", file_name, e))
+        .map_err(|e| format!("Failed to write results to '{}': {}", file_name, e))?;
+    let file_name = format!("{}/{}.subsumption", output_directory, subj_file.split('/').last().unwrap());
+    write_subsumption_graphs(subjects, &file_name)
+        .map_err(|e| format!("Failed to write results to '{}': {}", file_name, e))?;
+    if config.format != OutputFormat::EquivClass {
+        let file_name = format!("{}/{}.{}", output_directory, subj_file.split('/').last().unwrap(), config.format);
+        write_records(subjects, config.format, config.mutants_log.as_deref(), &file_name)
+            .map_err(|e| format!("Failed to write results to '{}': {}", file_name, e))?;
+    }
+    if subjects.subjects.iter().any(|subject| subject.analysis_result.explanation.is_some()) {
+        let file_name = format!("{}/{}.explanation", output_directory, subj_file.split('/').last().unwrap());
+        write_explanations(subjects, &file_name)
+            .map_err(|e| format!("Failed to write results to '{}': {}", file_name, e))?;
+    }
+    Ok(())
 }
 
 fn print_summary(global_data: RuntimeMetrics) {
     println!("        SUMMARY");
     println!("        =======");
     println!("Mutants found: {}", global_data.total_mutants);
-    println!("Equivs found: {}", global_data.total_discovered_equivalences);
+    println!("Equivalent mutants: {}", global_data.total_equivalent_mutants);
+    println!("Duplicate mutants: {}", global_data.total_duplicate_mutants);
+    println!("Dominator mutants: {}", global_data.total_dominators);
+    if !global_data.breakdown.is_empty() {
+        print_breakdown(&global_data.breakdown);
+    }
 }
 
-pub fn write_subjects_to_single_file(subjects: &Subjects, file: &str) -> Result<(), Error> {
-    let mut file = File::create(file)?;
-    let mut equiv_file_contents = vec![];
-    for subject in &subjects.subjects {
-        equiv_file_contents.push(get_equiv_file_contents_for_subject(subject));
+/// Print the equivalent and duplicate mutants found out of those analyzed, by
+/// operator and method, and the lines where any were found
+fn print_breakdown(breakdown: &Breakdown) {
+    println!("By operator:");
+    for (operator, tally) in &breakdown.by_operator {
+        println!("    {}: {}", operator, format_tally(tally));
+    }
+    println!("By method:");
+    for (method, tally) in &breakdown.by_method {
+        println!("    {}: {}", method, format_tally(tally));
+    }
+    println!("By line:");
+    let found = |tally: &Tally| tally.equivalences + tally.duplicates > 0;
+    for ((method, line), tally) in breakdown.by_line.iter().filter(|(_, tally)| found(tally)) {
+        println!("    {}:{}: {}", method, line, format_tally(tally));
     }
+}
 
-    let file_contents = equiv_file_contents.join("\n");
-    file.write_all(file_contents.as_bytes())
+fn format_tally(tally: &Tally) -> String {
+    format!("{} equivalent, {} duplicate of {}", tally.equivalences, tally.duplicates, tally.mutants)
 }
 
-fn get_equiv_file_contents_for_subject(subject: &Subject) -> String {
-    let ar = &subject.analysis_result;
-    let mut equiv_classes_as_strings = vec![];
-    for equiv_class in &ar.equivalence_classes {
-        if equiv_class.len() == 1 && equiv_class.iter().next().unwrap() == &0 {
-            continue;
-        }
-        let equiv_class_as_string: String = itertools::sorted(equiv_class)
-            .iter()
-            .map(|id| (**id).to_string())
-            .intersperse(" ".to_string())
-            .collect();
-        equiv_classes_as_strings.push(equiv_class_as_string);
-    }
-    format!("{}\n", equiv_classes_as_strings.join("\n"))
+/// Write each subject's subsumption graph as a Graphviz digraph, with an edge
+/// from each group of mutants to the groups it subsumes
+pub fn write_subsumption_graphs(subjects: &Subjects, file: &str) -> Result<(), Error> {
+    let mut file = File::create(file)?;
+    let mut graphs = vec![];
+    for subject in &subjects.subjects {
+        let graph = &subject.analysis_result.subsumption;
+        let label = |mids: &Vec<u32>| format!("\"{}\"", mids.iter().map(u32::to_string).join(" "));
+        let mut lines = vec![
+            format!("digraph \"{}\" {{", subject.method),
+            format!("    // dominators: {}", graph.dominators.iter().map(u32::to_string).join(" ")),
+        ];
+        lines.extend(graph.nodes.iter().map(|mids| format!("    {};", label(mids))));
+        lines.extend(graph.edges.iter()
+            .map(|&(i, j)| format!("    {} -> {};", label(&graph.nodes[i]), label(&graph.nodes[j]))));
+        lines.push("}".to_string());
+        graphs.push(lines.join("\n"));
+    }
+    file.write_all(format!("{}\n", graphs.join("\n\n")).as_bytes())
 }
 
+pub fn write_explanations(subjects: &Subjects, file: &str) -> Result<(), Error> {
+    let mut file = File::create(file)?;
+    let explanations: Vec<&str> = subjects.subjects.iter()
+        .filter_map(|subject| subject.analysis_result.explanation.as_deref())
+        .collect();
+    file.write_all(format!("{}\n", explanations.join("\n\n")).as_bytes())
+}
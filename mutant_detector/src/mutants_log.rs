@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// A row of Major's `mutants.log`:
+///
+/// ```text
+/// id:OPERATOR:original:replacement:method:line:transformation
+/// ```
+#[derive(Clone, Debug)]
+pub struct MutantsLogRow {
+    pub mid: u32,
+    /// The mutation operator, such as `AOR` or `ROR`
+    pub operator: String,
+    pub original: String,
+    pub replacement: String,
+    /// The mutated method, as `Class@name(arg,types)`
+    pub method: String,
+    pub line: u32,
+    /// The mutated source, as `a + b |==> a - b`
+    pub transformation: String,
+}
+
+/// The mutants of a Major run, by mutant id
+#[derive(Clone, Debug, Default)]
+pub struct MutantsLog {
+    pub file: String,
+    pub rows: HashMap<u32, MutantsLogRow>,
+}
+
+impl MutantsLog {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read mutants log '{}': {}", file, e))?;
+        let mut rows = HashMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = parse_row(line).map_err(|e| format!("{}:{}: {}", file, index + 1, e))?;
+            if rows.contains_key(&row.mid) {
+                return Err(format!("{}:{}: duplicate mutant id {}", file, index + 1, row.mid));
+            }
+            rows.insert(row.mid, row);
+        }
+        Ok(MutantsLog { file: file.to_string(), rows })
+    }
+
+    pub fn get(&self, mid: u32) -> Option<&MutantsLogRow> {
+        self.rows.get(&mid)
+    }
+}
+
+fn parse_row(line: &str) -> Result<MutantsLogRow, String> {
+    // The transformation is source code, and may itself hold colons
+    let fields: Vec<&str> = line.splitn(7, ':').collect();
+    if fields.len() != 7 {
+        return Err(format!("expected 'id:OPERATOR:original:replacement:method:line:transformation', found '{}'", line));
+    }
+    let number = |field: &str, what: &str| {
+        field.parse::<u32>().map_err(|_| format!("{} '{}' is not a number", what, field))
+    };
+    Ok(MutantsLogRow {
+        mid: number(fields[0], "mutant id")?,
+        operator: fields[1].to_string(),
+        original: fields[2].to_string(),
+        replacement: fields[3].to_string(),
+        method: fields[4].to_string(),
+        line: number(fields[5], "line number")?,
+        transformation: fields[6].to_string(),
+    })
+}
+
+/// How many mutants were analyzed, how many of them were equivalent to the
+/// original, and how many were duplicates of another mutant
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub mutants: u32,
+    pub equivalences: u32,
+    pub duplicates: u32,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.mutants += other.mutants;
+        self.equivalences += other.equivalences;
+        self.duplicates += other.duplicates;
+    }
+}
+
+/// Tallies of the analyzed mutants by operator, method and line
+#[derive(Debug, Default)]
+pub struct Breakdown {
+    pub by_operator: BTreeMap<String, Tally>,
+    pub by_method: BTreeMap<String, Tally>,
+    pub by_line: BTreeMap<(String, u32), Tally>,
+}
+
+impl Breakdown {
+    /// Count the mutant of `row`, which is `equivalent` to the original, a
+    /// `duplicate` of another mutant, or neither
+    pub fn record(&mut self, row: &MutantsLogRow, equivalent: bool, duplicate: bool) {
+        let tally = Tally { mutants: 1, equivalences: equivalent as u32, duplicates: duplicate as u32 };
+        self.by_operator.entry(row.operator.clone()).or_default().add(tally);
+        self.by_method.entry(row.method.clone()).or_default().add(tally);
+        self.by_line.entry((row.method.clone(), row.line)).or_default().add(tally);
+    }
+
+    pub fn merge(&mut self, other: Breakdown) {
+        for (operator, tally) in other.by_operator {
+            self.by_operator.entry(operator).or_default().add(tally);
+        }
+        for (method, tally) in other.by_method {
+            self.by_method.entry(method).or_default().add(tally);
+        }
+        for (line, tally) in other.by_line {
+            self.by_line.entry(line).or_default().add(tally);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_operator.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<MutantsLog, String> {
+        let file = std::env::temp_dir().join(name);
+        fs::write(&file, contents).unwrap();
+        MutantsLog::load(file.to_str().unwrap()).map_err(|e| e.replace(file.to_str().unwrap(), "mutants.log"))
+    }
+
+    #[test]
+    fn parses_rows_with_colons_in_the_transformation() {
+        let row = parse_row("12:COR:a?b:a:Foo@pick(boolean,int,int):7:c ? a : b |==> a").unwrap();
+        assert_eq!(row.mid, 12);
+        assert_eq!(row.operator, "COR");
+        assert_eq!((row.original.as_str(), row.replacement.as_str()), ("a?b", "a"));
+        assert_eq!(row.method, "Foo@pick(boolean,int,int)");
+        assert_eq!(row.line, 7);
+        assert_eq!(row.transformation, "c ? a : b |==> a");
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        assert!(parse_row("1:AOR:+:-:Foo@f()").unwrap_err().starts_with("expected 'id:OPERATOR"));
+        assert_eq!(parse_row("x:AOR:+:-:Foo@f():3:a + b |==> a - b").unwrap_err(), "mutant id 'x' is not a number");
+        assert_eq!(parse_row("1:AOR:+:-:Foo@f():y:a + b |==> a - b").unwrap_err(), "line number 'y' is not a number");
+    }
+
+    #[test]
+    fn loads_rows_by_id_and_reports_the_failing_line() {
+        let log = load(
+            "mutants_log_tests_ok.log",
+            "1:AOR:+:-:Foo@f():3:a + b |==> a - b\n\n2:ROR:<:<=:Foo@f():4:a < b |==> a <= b\n",
+        ).unwrap();
+        assert_eq!(log.rows.len(), 2);
+        assert_eq!(log.get(2).unwrap().operator, "ROR");
+        assert!(log.get(3).is_none());
+
+        let error = load("mutants_log_tests_bad.log", "1:AOR:+:-:Foo@f():3:a + b |==> a - b\n2:ROR\n").unwrap_err();
+        assert!(error.starts_with("mutants.log:2: expected"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let error = load(
+            "mutants_log_tests_duplicate.log",
+            "1:AOR:+:-:Foo@f():3:a + b |==> a - b\n1:AOR:+:*:Foo@f():3:a + b |==> a * b\n",
+        ).unwrap_err();
+        assert_eq!(error, "mutants.log:2: duplicate mutant id 1");
+    }
+
+    #[test]
+    fn breakdowns_tally_equivalent_and_duplicate_mutants() {
+        let row = parse_row("1:AOR:+:-:Foo@f():3:a + b |==> a - b").unwrap();
+        let mut breakdown = Breakdown::default();
+        breakdown.record(&row, true, false);
+        breakdown.record(&row, false, true);
+        let mut other = Breakdown::default();
+        other.record(&row, false, false);
+        breakdown.merge(other);
+        let tally = Tally { mutants: 3, equivalences: 1, duplicates: 1 };
+        assert_eq!(breakdown.by_operator["AOR"], tally);
+        assert_eq!(breakdown.by_method["Foo@f()"], tally);
+        assert_eq!(breakdown.by_line[&("Foo@f()".to_string(), 3)], tally);
+    }
+}
@@ -1,5 +1,10 @@
 use egg::*;
-use crate::primitives::{JavaLong, JavaInt, IsZero};
+use crate::interval::Interval;
+use crate::purity;
+use crate::primitives::{
+    JavaLong, JavaInt, JavaShort, JavaByte, JavaChar, JavaFloat, JavaDouble, JavaString,
+    JavaNumber, NumericType, Promoted, UnsignedShr,
+};
 
 pub type EGraph = egg::EGraph<Peg, PegAnalysis>;
 
@@ -8,6 +13,16 @@ define_language! {
     Num(JavaInt),
     Long(JavaLong),
     Bool(bool),
+    // Literals of the remaining primitive types, and string literals. Each
+    // has a distinct spelling (see `primitives.rs`), so the order in which
+    // they are tried when parsing only matters relative to `Symbol`, which
+    // accepts anything and must come last
+    Float(JavaFloat),
+    Double(JavaDouble),
+    Short(JavaShort),
+    Byte(JavaByte),
+    Char(JavaChar),
+    Str(JavaString),
     // A generic error. This is a stand in for all exceptional behavior and is
     // unsound.
     // TODO: handle exceptions explicitly
@@ -89,6 +104,11 @@ define_language! {
     "invoke-static" = InvokeStatic([Id; 3]),
     // Method name that is being invoked
     "method" = MethodName(Id),
+    // (pure-call method receiver actuals)
+    // A call to a method listed in the `PurityTable`, which neither throws nor
+    // touches the heap. Its value only depends on its receiver (`unit` for
+    // static methods) and actuals, so unlike `invoke` it takes no heap
+    "pure-call" = PureCall([Id; 3]),
     // Actual paramters passed to a method
     "actuals" = Actuals(Box<[Id]>),
     // A list of exit conditions: these are not rewritten for now to avoid AC
@@ -178,21 +198,104 @@ define_language! {
   }
 }
 
-pub fn is_const(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+pub fn is_const(v1: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let v1: egg::Var = v1.parse().unwrap();
     move |egraph, _, subst| egraph[subst[v1]].data.constant.is_some()
 }
 
-pub fn is_not_const(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+pub fn is_not_const(v1: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let v1: egg::Var = v1.parse().unwrap();
     move |egraph, _, subst| !egraph[subst[v1]].data.constant.is_some()
 }
 
-pub fn is_var(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+pub fn is_var(v1: &str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let v1: egg::Var = v1.parse().unwrap();
     move |egraph, _, subst| egraph[subst[v1]].data.variable.is_some()
 }
 
+fn range(egraph: &EGraph, subst: &Subst, v: egg::Var) -> Option<Interval> {
+    egraph[subst[v]].data.range
+}
+
+/// `v1` is known to be of an integral type. Many rules don't hold for `float`
+/// and `double`, because of rounding, NaN and signed zeros
+pub fn is_integral(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| egraph[subst[v1]].data.numeric.map_or(false, NumericType::is_integral)
+}
+
+/// `v1` is known to be of a numeric primitive type, so that `+` adds rather
+/// than concatenates
+pub fn is_numeric(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| egraph[subst[v1]].data.numeric.is_some()
+}
+
+/// `v1` is known to be of the primitive type `ty` (not, say, a `String`, or a
+/// narrower type that a call would widen)
+pub fn has_type(v1: &'static str, ty: NumericType) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| egraph[subst[v1]].data.numeric == Some(ty)
+}
+
+/// `v1` is known to be a non-negative int or long
+pub fn is_nonneg(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| range(egraph, subst, v1).map_or(false, |r| r.is_nonneg())
+}
+
+/// `v1` is known to be an int or long other than 0
+pub fn is_nonzero(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| range(egraph, subst, v1).map_or(false, |r| !r.contains_zero())
+}
+
+/// `v1` is known to be an int (after promotion), with a known range
+pub fn is_int(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| range(egraph, subst, v1).map_or(false, |r| !r.is_long)
+}
+
+/// `v1` is known to be a long, with a known range
+pub fn is_long(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| range(egraph, subst, v1).map_or(false, |r| r.is_long)
+}
+
+/// `(op a b)` is known not to overflow (and, for `/` and `%`, not to throw),
+/// where `op` is one of `+`, `-`, `*`, `/` and `%`
+pub fn fits_without_overflow(
+    op: &'static str,
+    a: &'static str,
+    b: &'static str,
+) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let a: egg::Var = a.parse().unwrap();
+    let b: egg::Var = b.parse().unwrap();
+    move |egraph, _, subst| match (range(egraph, subst, a), range(egraph, subst, b)) {
+        (Some(a), Some(b)) => Interval::binary(op, a, b).is_some(),
+        _ => false,
+    }
+}
+
+/// Evaluating `v1` is known not to throw or have effects (see
+/// `PegAnalysisData::pure`)
+pub fn is_pure(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, _, subst| egraph[subst[v1]].data.pure
+}
+
+/// Shifting by `b` and then by `c` is the same as shifting by `b + c`: both
+/// distances are known to be non-negative, and their sum is below 32, so that
+/// masking the distance never comes into play for int or long operands
+pub fn shifts_compose(b: &'static str, c: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let b: egg::Var = b.parse().unwrap();
+    let c: egg::Var = c.parse().unwrap();
+    move |egraph, _, subst| match (range(egraph, subst, b), range(egraph, subst, c)) {
+        (Some(b), Some(c)) => b.is_nonneg() && c.is_nonneg() && (b.hi as i128 + c.hi as i128) < 32,
+        _ => false,
+    }
+}
+
 pub fn are_bit_disjoint_constants(a: &'static str, b: &'static str) 
     -> impl Fn(&mut EGraph, Id, &Subst) -> bool
 {
@@ -218,14 +321,55 @@ pub fn are_bit_disjoint_constants(a: &'static str, b: &'static str)
 }
 
 pub fn is_not_same_var(
-    v1: &'static str,
-    v2: &'static str,
+    v1: &str,
+    v2: &str,
 ) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let v1: egg::Var = v1.parse().unwrap();
     let v2: egg::Var = v2.parse().unwrap();
     move |egraph: &mut EGraph, _, subst: &Subst| egraph.find(subst[v1]) != egraph.find(subst[v2])
 }
 
+/// `v1` is bound to the class the rule's left hand side matched
+pub fn is_matched_class(v1: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let v1: egg::Var = v1.parse().unwrap();
+    move |egraph, id, subst| egraph.find(subst[v1]) == egraph.find(id)
+}
+
+/// The last field dereferenced by each `path` node in class `id`
+fn last_fields(egraph: &EGraph, id: Id) -> Vec<egg::Symbol> {
+    let symbol = |id: Id| egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::Symbol(s) => Some(*s),
+        _ => None,
+    });
+    let derefs = |id: Id| egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::Derefs(s) => symbol(*s),
+        _ => None,
+    });
+    egraph[id].nodes.iter()
+        .filter_map(|node| match node {
+            Peg::AccessPath([_, d]) => derefs(*d),
+            _ => None,
+        })
+        .map(|derefs| derefs.as_str().rsplit('.').next().unwrap().into())
+        .collect()
+}
+
+/// The access paths `p` and `q` are known to name different heap locations.
+///
+/// A location is an object together with a field, so paths ending in
+/// differently named fields never alias, whatever their bases. Paths that end
+/// in the same field may alias even when their deref strings differ: `a.x` and
+/// `b.x` on the same base are the same location whenever `a` and `b` are.
+pub fn are_distinct_paths(p: &'static str, q: &'static str) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let p: egg::Var = p.parse().unwrap();
+    let q: egg::Var = q.parse().unwrap();
+    move |egraph, _, subst| {
+        let q_fields = last_fields(egraph, subst[q]);
+        last_fields(egraph, subst[p]).iter()
+            .any(|p_field| q_fields.iter().any(|q_field| p_field != q_field))
+    }
+}
+
 impl Peg {
     pub fn as_bool(&self) -> Option<bool> {
         match self {
@@ -236,7 +380,9 @@ impl Peg {
 
     pub fn is_const(&self) -> bool {
         match self {
-            Peg::Num(_) | Peg::Bool(_) | Peg::Long(_) => true,
+            Peg::Num(_) | Peg::Bool(_) | Peg::Long(_)
+                | Peg::Float(_) | Peg::Double(_) | Peg::Short(_) | Peg::Byte(_)
+                | Peg::Char(_) | Peg::Str(_) => true,
             _ => false,
         }
     }
@@ -278,165 +424,239 @@ impl Peg {
         }
     }
 
-    pub fn plus(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a + *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a + *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a + *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a + *b)),
+    pub fn as_number(&self) -> Option<JavaNumber> {
+        match self {
+            Peg::Num(n) => Some(JavaNumber::Int(*n)),
+            Peg::Long(n) => Some(JavaNumber::Long(*n)),
+            Peg::Float(x) => Some(JavaNumber::Float(*x)),
+            Peg::Double(x) => Some(JavaNumber::Double(*x)),
+            Peg::Short(n) => Some(JavaNumber::Short(*n)),
+            Peg::Byte(n) => Some(JavaNumber::Byte(*n)),
+            Peg::Char(c) => Some(JavaNumber::Char(*c)),
             _ => None,
         }
     }
 
-    pub fn equal(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a == b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a == b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a == b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a == b)),
-            (Peg::Bool(a), Peg::Bool(b)) => Some(Peg::Bool(a == b)),
+    /// Both operands, after binary numeric promotion
+    fn promote(a: &Peg, b: &Peg) -> Option<Promoted> {
+        Some(JavaNumber::binary_promotion(a.as_number()?, b.as_number()?))
+    }
+
+    /// Both operands of a bitwise operator: integral values after binary
+    /// numeric promotion
+    fn promote_integral(a: &Peg, b: &Peg) -> Option<Promoted> {
+        match Peg::promote(a, b)? {
+            p @ Promoted::Int(..) | p @ Promoted::Long(..) => Some(p),
             _ => None,
         }
     }
 
-    pub fn nequal(a: &Peg, b: &Peg) -> Option<Peg> {
+    /// The operands of a shift, which are promoted separately (JLS §15.19)
+    fn promote_shift(a: &Peg, b: &Peg) -> Option<(JavaNumber, JavaNumber)> {
+        let a = a.as_number()?.unary_promotion();
+        let b = b.as_number()?.unary_promotion();
         match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a != b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a != b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a != b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a != b)),
-            (Peg::Bool(a), Peg::Bool(b)) => Some(Peg::Bool(a != b)),
+            (JavaNumber::Int(_), _) | (JavaNumber::Long(_), _) => match b {
+                JavaNumber::Int(_) | JavaNumber::Long(_) => Some((a, b)),
+                _ => None,
+            },
             _ => None,
         }
     }
 
-    pub fn lt(a: &Peg, b: &Peg) -> Option<Peg> {
+    /// String concatenation, where the other operand's string conversion is
+    /// the same in Rust as in Java (not the case for floating point values)
+    fn concat(a: &Peg, b: &Peg) -> Option<Peg> {
+        fn to_java_string(p: &Peg) -> Option<String> {
+            match p {
+                Peg::Str(s) => Some(s.contents()),
+                Peg::Num(n) => Some(n.to_string()),
+                Peg::Long(n) => Some(n.to_string()),
+                Peg::Bool(b) => Some(b.to_string()),
+                _ => None,
+            }
+        }
         match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a.lt(b))),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a.lt(b))),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a.promote_to_java_long().lt(b))),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a.lt(&b.promote_to_java_long()))),
+            (Peg::Str(_), _) | (_, Peg::Str(_)) => {
+                let s = to_java_string(a)? + &to_java_string(b)?;
+                Some(Peg::Str(JavaString::from(s.as_str())))
+            }
             _ => None,
         }
     }
 
-    pub fn le(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a.le(b))),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a.le(b))),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a.promote_to_java_long().le(b))),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a.le(&b.promote_to_java_long()))),
-            _ => None,
+    pub fn plus(a: &Peg, b: &Peg) -> Option<Peg> {
+        if let Some(s) = Peg::concat(a, b) {
+            return Some(s);
         }
+        Some(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => Peg::Num(a + b),
+            Promoted::Long(a, b) => Peg::Long(a + b),
+            Promoted::Float(a, b) => Peg::Float(a + b),
+            Promoted::Double(a, b) => Peg::Double(a + b),
+        })
     }
 
-    pub fn gt(a: &Peg, b: &Peg) -> Option<Peg> {
+    // Floating point comparisons go through `value()`: the `Eq` and `Ord`
+    // instances of `JavaFloat` and `JavaDouble` compare bit patterns. String
+    // literals are interned, so `==` on two of them is equality of their
+    // contents; `eval` doesn't fold it on other strings
+
+    pub fn equal(a: &Peg, b: &Peg) -> Option<Peg> {
         match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a.gt(b))),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a.gt(b))),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a.promote_to_java_long().gt(b))),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a.gt(&b.promote_to_java_long()))),
-            _ => None,
+            (Peg::Bool(a), Peg::Bool(b)) => return Some(Peg::Bool(a == b)),
+            (Peg::Str(a), Peg::Str(b)) => return Some(Peg::Bool(a == b)),
+            _ => (),
         }
+        Some(Peg::Bool(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => a == b,
+            Promoted::Long(a, b) => a == b,
+            Promoted::Float(a, b) => a.value() == b.value(),
+            Promoted::Double(a, b) => a.value() == b.value(),
+        }))
+    }
+
+    pub fn nequal(a: &Peg, b: &Peg) -> Option<Peg> {
+        Peg::equal(a, b).as_ref().and_then(Peg::not)
+    }
+
+    pub fn lt(a: &Peg, b: &Peg) -> Option<Peg> {
+        Some(Peg::Bool(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => a.lt(&b),
+            Promoted::Long(a, b) => a.lt(&b),
+            Promoted::Float(a, b) => a.value() < b.value(),
+            Promoted::Double(a, b) => a.value() < b.value(),
+        }))
+    }
+
+    pub fn le(a: &Peg, b: &Peg) -> Option<Peg> {
+        Some(Peg::Bool(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => a.le(&b),
+            Promoted::Long(a, b) => a.le(&b),
+            Promoted::Float(a, b) => a.value() <= b.value(),
+            Promoted::Double(a, b) => a.value() <= b.value(),
+        }))
+    }
+
+    pub fn gt(a: &Peg, b: &Peg) -> Option<Peg> {
+        Some(Peg::Bool(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => a.gt(&b),
+            Promoted::Long(a, b) => a.gt(&b),
+            Promoted::Float(a, b) => a.value() > b.value(),
+            Promoted::Double(a, b) => a.value() > b.value(),
+        }))
     }
 
     pub fn ge(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a.ge(b))),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a.ge(b))),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a.promote_to_java_long().ge(b))),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Bool(a.ge(&b.promote_to_java_long()))),
-            _ => None,
-        }
+        Some(Peg::Bool(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => a.ge(&b),
+            Promoted::Long(a, b) => a.ge(&b),
+            Promoted::Float(a, b) => a.value() >= b.value(),
+            Promoted::Double(a, b) => a.value() >= b.value(),
+        }))
     }
 
     pub fn minus(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a - *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a - *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a - *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a - *b)),
-            _ => None,
-        }
+        Some(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => Peg::Num(a - b),
+            Promoted::Long(a, b) => Peg::Long(a - b),
+            Promoted::Float(a, b) => Peg::Float(a - b),
+            Promoted::Double(a, b) => Peg::Double(a - b),
+        })
     }
 
     pub fn mult(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a * *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a * *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a * *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a * *b)),
-            _ => None,
-        }
+        Some(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => Peg::Num(a * b),
+            Promoted::Long(a, b) => Peg::Long(a * b),
+            Promoted::Float(a, b) => Peg::Float(a * b),
+            Promoted::Double(a, b) => Peg::Double(a * b),
+        })
     }
 
+    // Integral division by zero throws; floating point division doesn't
+
     pub fn div(a: &Peg, b: &Peg) -> Option<Peg> {
-        match b {
-            Peg::Num(b) => if b.is_zero() { return Some(Peg::Error)},
-            Peg::Long(b) => if b.is_zero() { return Some(Peg::Error)},
-            _ => return None,
-        }
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a / *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a / *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a / *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a / *b)),
-            _ => None,
-        }
+        Some(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => (a / b).map_or(Peg::Error, Peg::Num),
+            Promoted::Long(a, b) => (a / b).map_or(Peg::Error, Peg::Long),
+            Promoted::Float(a, b) => Peg::Float(a / b),
+            Promoted::Double(a, b) => Peg::Double(a / b),
+        })
+    }
+
+    pub fn rem(a: &Peg, b: &Peg) -> Option<Peg> {
+        Some(match Peg::promote(a, b)? {
+            Promoted::Int(a, b) => (a % b).map_or(Peg::Error, Peg::Num),
+            Promoted::Long(a, b) => (a % b).map_or(Peg::Error, Peg::Long),
+            Promoted::Float(a, b) => Peg::Float(a % b),
+            Promoted::Double(a, b) => Peg::Double(a % b),
+        })
     }
 
+    // On booleans, `&`, `|` and `^` are the non-short-circuiting logical
+    // operators
+
     pub fn bin_and(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a & *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a & *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a & *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a & *b)),
+        if let (Peg::Bool(a), Peg::Bool(b)) = (a, b) {
+            return Some(Peg::Bool(*a & *b));
+        }
+        match Peg::promote_integral(a, b)? {
+            Promoted::Int(a, b) => Some(Peg::Num(a & b)),
+            Promoted::Long(a, b) => Some(Peg::Long(a & b)),
             _ => None,
         }
     }
 
     pub fn bin_or(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a | *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a | *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a | *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a | *b)),
+        if let (Peg::Bool(a), Peg::Bool(b)) = (a, b) {
+            return Some(Peg::Bool(*a | *b));
+        }
+        match Peg::promote_integral(a, b)? {
+            Promoted::Int(a, b) => Some(Peg::Num(a | b)),
+            Promoted::Long(a, b) => Some(Peg::Long(a | b)),
             _ => None,
         }
     }
 
     pub fn xor(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a ^ *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a ^ *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a ^ *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a ^ *b)),
+        if let (Peg::Bool(a), Peg::Bool(b)) = (a, b) {
+            return Some(Peg::Bool(*a ^ *b));
+        }
+        match Peg::promote_integral(a, b)? {
+            Promoted::Int(a, b) => Some(Peg::Num(a ^ b)),
+            Promoted::Long(a, b) => Some(Peg::Long(a ^ b)),
             _ => None,
         }
     }
 
+    // The type of a shift is the type of its left operand (JLS §15.19)
     pub fn srshift(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a >> *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a >> *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a >> *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a >> *b)),
+        match Peg::promote_shift(a, b)? {
+            (JavaNumber::Int(a), JavaNumber::Int(b)) => Some(Peg::Num(a >> b)),
+            (JavaNumber::Long(a), JavaNumber::Long(b)) => Some(Peg::Long(a >> b)),
+            (JavaNumber::Int(a), JavaNumber::Long(b)) => Some(Peg::Num(a >> b)),
+            (JavaNumber::Long(a), JavaNumber::Int(b)) => Some(Peg::Long(a >> b)),
             _ => None,
         }
     }
 
     pub fn urshift(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
+        match Peg::promote_shift(a, b)? {
+            (JavaNumber::Int(a), JavaNumber::Int(b)) => Some(Peg::Num(a.unsigned_shr(b))),
+            (JavaNumber::Long(a), JavaNumber::Long(b)) => Some(Peg::Long(a.unsigned_shr(b))),
+            (JavaNumber::Int(a), JavaNumber::Long(b)) => Some(Peg::Num(a.unsigned_shr(b))),
+            (JavaNumber::Long(a), JavaNumber::Int(b)) => Some(Peg::Long(a.unsigned_shr(b))),
             _ => None,
         }
     }
 
     pub fn lshift(a: &Peg, b: &Peg) -> Option<Peg> {
-        match (a, b) {
-            (Peg::Num(a), Peg::Num(b)) => Some(Peg::Num(*a << *b)),
-            (Peg::Long(a), Peg::Long(b)) => Some(Peg::Long(*a << *b)),
-            (Peg::Num(a), Peg::Long(b)) => Some(Peg::Long(*a << *b)),
-            (Peg::Long(a), Peg::Num(b)) => Some(Peg::Long(*a << *b)),
+        match Peg::promote_shift(a, b)? {
+            (JavaNumber::Int(a), JavaNumber::Int(b)) => Some(Peg::Num(a << b)),
+            (JavaNumber::Long(a), JavaNumber::Long(b)) => Some(Peg::Long(a << b)),
+            (JavaNumber::Int(a), JavaNumber::Long(b)) => Some(Peg::Num(a << b)),
+            (JavaNumber::Long(a), JavaNumber::Int(b)) => Some(Peg::Long(a << b)),
             _ => None,
         }
     }
@@ -456,19 +676,62 @@ impl Peg {
     }
 
     pub fn neg(a: &Peg) -> Option<Peg> {
+        match a.as_number()?.unary_promotion() {
+            JavaNumber::Int(x) => Some(Peg::Num(-x)),
+            JavaNumber::Long(x) => Some(Peg::Long(-x)),
+            JavaNumber::Float(x) => Some(Peg::Float(-x)),
+            JavaNumber::Double(x) => Some(Peg::Double(-x)),
+            _ => None,
+        }
+    }
+
+    pub fn bin_neg(a: &Peg) -> Option<Peg> {
+        match a.as_number()?.unary_promotion() {
+            JavaNumber::Int(x) => Some(Peg::Num(!x)),
+            JavaNumber::Long(x) => Some(Peg::Long(!x)),
+            _ => None,
+        }
+    }
+
+    pub fn not(a: &Peg) -> Option<Peg> {
         match a {
-            Peg::Num(x) => Some(Peg::Num(- *x)),
-            Peg::Long(x) => Some(Peg::Long(- *x)),
+            Peg::Bool(b) => Some(Peg::Bool(!*b)),
             _ => None,
         }
     }
 }
 
+impl From<JavaNumber> for Peg {
+    fn from(n: JavaNumber) -> Self {
+        match n {
+            JavaNumber::Byte(n) => Peg::Byte(n),
+            JavaNumber::Short(n) => Peg::Short(n),
+            JavaNumber::Char(c) => Peg::Char(c),
+            JavaNumber::Int(n) => Peg::Num(n),
+            JavaNumber::Long(n) => Peg::Long(n),
+            JavaNumber::Float(x) => Peg::Float(x),
+            JavaNumber::Double(x) => Peg::Double(x),
+        }
+    }
+}
+
 /// Data tracked for a PegAnalysis
 #[derive(Default, PartialEq, Debug, Clone)]
 pub struct PegAnalysisData {
     pub constant: Option<Peg>,
     pub variable: Option<Peg>,
+    /// The values this class may take, when it is known to be an int or long
+    /// that doesn't overflow. Besides constants, ranges come from the declared
+    /// types of variables and from casts
+    pub range: Option<Interval>,
+    /// The primitive numeric type of this class, when it is known: from a
+    /// constant, a variable's declared type or a cast, and then through
+    /// numeric promotion
+    pub numeric: Option<NumericType>,
+    /// Evaluating this class can't throw, read the heap or call a method (other
+    /// than a `pure-call`), so it is safe to evaluate it more, less often, or
+    /// in a different order
+    pub pure: bool,
 }
 
 impl PegAnalysisData {
@@ -484,9 +747,16 @@ impl PegAnalysisData {
         //     panic!("Illegal state reached: trying to merge multiple variable values {:?} {:?}", self, a)
         // }
 
+        let range = match (self.range, a.range) {
+            (Some(r1), Some(r2)) => Interval::intersect(r1, r2).or(Some(r1)),
+            (r1, r2) => r1.or(r2),
+        };
         PegAnalysisData {
             constant: self.constant.or(a.constant),
             variable: self.variable.or(a.variable),
+            range,
+            numeric: self.numeric.or(a.numeric),
+            pure: self.pure || a.pure,
         }
     }
 }
@@ -494,18 +764,182 @@ impl PegAnalysisData {
 #[derive(Default)]
 pub struct PegAnalysis;
 
+/// The type named by one of the `(type-name "...")` nodes in class `id`, when
+/// it is `boolean` (`None` inside the `Some`) or a primitive numeric type
+fn primitive_type_name(egraph: &EGraph, id: Id) -> Option<Option<NumericType>> {
+    egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::TypeName(name) => match &egraph[*name].data.constant {
+            Some(Peg::Str(name)) => match name.contents().as_str() {
+                "boolean" => Some(None),
+                name => NumericType::from_name(name).map(Some),
+            },
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// The primitive numeric type named by the type annotation of a `var`, which
+/// is a string literal like `"int"`
+fn declared_type(egraph: &EGraph, annotation: Id) -> Option<NumericType> {
+    match &egraph[annotation].data.constant {
+        Some(Peg::Str(name)) => NumericType::from_name(name.contents().as_str()),
+        _ => None,
+    }
+}
+
+/// The numeric type of `enode`'s value, from the types of its children
+fn numeric_type_of(egraph: &EGraph, enode: &Peg) -> Option<NumericType> {
+    let t = |i: &Id| egraph[*i].data.numeric;
+
+    match enode {
+        Peg::Add([a, b])
+        | Peg::Sub([a, b])
+        | Peg::Mul([a, b])
+        | Peg::Div([a, b])
+        | Peg::Rem([a, b]) => Some(NumericType::binary(t(a)?, t(b)?)),
+        Peg::BinAnd([a, b]) | Peg::BinOr([a, b]) | Peg::Xor([a, b]) => {
+            let ty = NumericType::binary(t(a)?, t(b)?);
+            if ty.is_integral() { Some(ty) } else { None }
+        }
+        Peg::Neg(a) | Peg::BinNeg(a) => Some(t(a)?.promoted()),
+        Peg::SRShift([a, _]) | Peg::URShift([a, _]) | Peg::LShift([a, _]) => Some(t(a)?.promoted()),
+        Peg::Cast([_, t]) => primitive_type_name(egraph, *t)?,
+        Peg::Phi([_, a, b]) if t(a) == t(b) => t(a),
+        _ => None,
+    }
+}
+
+/// Whether `enode` is pure, given the purity of its children. Integral
+/// division can throw unless its divisor is known not to be 0, and casts can
+/// throw unless they are to a primitive type
+fn is_pure_node(egraph: &EGraph, enode: &Peg) -> bool {
+    let children_pure = || enode.children().iter().all(|c| egraph[*c].data.pure);
+    match enode {
+        Peg::Error
+        | Peg::Rd(..)
+        | Peg::Invoke(..)
+        | Peg::InvokeStatic(..)
+        | Peg::InvokeToPeg(..)
+        | Peg::InvokeToHeapState(..)
+        | Peg::InvokeToExceptionStatus(..)
+        | Peg::New(..)
+        | Peg::ArrayAccess(..) => false,
+        Peg::Div([_, b]) | Peg::Rem([_, b]) => {
+            egraph[*b].data.range.map_or(false, |r| !r.contains_zero()) && children_pure()
+        }
+        Peg::Cast([_, t]) => primitive_type_name(egraph, *t).is_some() && children_pure(),
+        _ => children_pure(),
+    }
+}
+
+/// The range of `enode`'s value, from the ranges of its children
+fn range_of(egraph: &EGraph, enode: &Peg) -> Option<Interval> {
+    let r = |i: &Id| egraph[*i].data.range;
+
+    match enode {
+        Peg::Add([a, b]) => Interval::binary("+", r(a)?, r(b)?),
+        Peg::Sub([a, b]) => Interval::binary("-", r(a)?, r(b)?),
+        Peg::Mul([a, b]) => Interval::binary("*", r(a)?, r(b)?),
+        Peg::Div([a, b]) => Interval::binary("/", r(a)?, r(b)?),
+        Peg::Rem([a, b]) => Interval::binary("%", r(a)?, r(b)?),
+        Peg::Neg(a) => Interval::neg(r(a)?),
+        Peg::BinAnd([a, b]) => Interval::bin_and(r(a)?, r(b)?),
+        Peg::SRShift([a, _]) => Interval::srshift(r(a)?),
+        Peg::URShift([a, _]) => Interval::urshift(r(a)?),
+        Peg::Phi([_, t, e]) => Interval::hull(r(t)?, r(e)?),
+        Peg::Cast([a, t]) => Interval::convert(r(a), primitive_type_name(egraph, *t)??),
+        _ => None,
+    }
+}
+
+/// Whether `==` can compare the values of classes `a` and `b`. On strings it
+/// compares references, which are only known to be the same when both are
+/// literals: other strings, like the results of concatenations, are new objects
+fn comparable_references(egraph: &EGraph, a: Id, b: Id) -> bool {
+    let is_literal = |id: Id| egraph[id].nodes.iter().any(|node| matches!(node, Peg::Str(_)));
+    match (&egraph[a].data.constant, &egraph[b].data.constant) {
+        (Some(Peg::Str(_)), _) | (_, Some(Peg::Str(_))) => is_literal(a) && is_literal(b),
+        _ => true,
+    }
+}
+
 fn eval(egraph: &EGraph, enode: &Peg) -> Option<Peg> {
     let x = |i: &Id| egraph[*i].data.constant.clone();
 
+    // Error is absorbing: an operator that evaluates an operand that throws
+    // throws too. `&&`, `||` and `phi` don't always evaluate all of theirs
+    let evaluates_operands = matches!(enode,
+        Peg::Add(_) | Peg::Sub(_) | Peg::Mul(_) | Peg::Div(_) | Peg::Rem(_) | Peg::Neg(_)
+        | Peg::BinNeg(_) | Peg::Not(_) | Peg::BinAnd(_) | Peg::BinOr(_) | Peg::Xor(_)
+        | Peg::SRShift(_) | Peg::URShift(_) | Peg::LShift(_) | Peg::Gte(_) | Peg::Gt(_)
+        | Peg::Lte(_) | Peg::Lt(_) | Peg::Equ(_) | Peg::Neq(_) | Peg::IsNull(_) | Peg::IsUnit(_)
+        | Peg::Cast(_) | Peg::CanCast(_));
+    if evaluates_operands && enode.children().iter().any(|c| x(c) == Some(Peg::Error)) {
+        return Some(Peg::Error);
+    }
+
     match enode {
         // Arithmetic
-        Peg::Long(_) | Peg::Num(_) | Peg::Bool(_) => Some(enode.clone()),
+        _ if enode.is_const() => Some(enode.clone()),
+        Peg::Error => Some(Peg::Error),
         Peg::Add([a, b]) => Peg::plus(&x(a)?, &x(b)?),
         Peg::Sub([a, b]) => Peg::minus(&x(a)?, &x(b)?),
         Peg::Mul([a, b]) => Peg::mult(&x(a)?, &x(b)?),
         Peg::Div([a, b]) => Peg::div(&x(a)?, &x(b)?),
+        Peg::Rem([a, b]) => Peg::rem(&x(a)?, &x(b)?),
 
         Peg::Neg(a) => Peg::neg(&x(a)?),
+        Peg::BinNeg(a) => Peg::bin_neg(&x(a)?),
+
+        // Boolean Ops. A `false` (`true`) on the left decides `&&` (`||`). On
+        // the right it only does when evaluating the left side can't throw
+        Peg::Not(a) => Peg::not(&x(a)?),
+        Peg::And([a, b]) => match (x(a), x(b)) {
+            (Some(Peg::Bool(false)), _) => Some(Peg::Bool(false)),
+            (Some(Peg::Error), _) | (Some(Peg::Bool(true)), Some(Peg::Error)) => Some(Peg::Error),
+            (_, Some(Peg::Bool(false))) if egraph[*a].data.pure => Some(Peg::Bool(false)),
+            (Some(a), Some(b)) => Peg::and(&a, &b),
+            _ => None,
+        },
+        Peg::Or([a, b]) => match (x(a), x(b)) {
+            (Some(Peg::Bool(true)), _) => Some(Peg::Bool(true)),
+            (Some(Peg::Error), _) | (Some(Peg::Bool(false)), Some(Peg::Error)) => Some(Peg::Error),
+            (_, Some(Peg::Bool(true))) if egraph[*a].data.pure => Some(Peg::Bool(true)),
+            (Some(a), Some(b)) => Peg::or(&a, &b),
+            _ => None,
+        },
+
+        // Null and unit checks: primitive constants are neither
+        Peg::IsNull(a) if egraph[*a].nodes.contains(&Peg::Null) => Some(Peg::Bool(true)),
+        Peg::IsUnit(a) if egraph[*a].nodes.contains(&Peg::Unit) => Some(Peg::Bool(true)),
+        Peg::IsNull(a) | Peg::IsUnit(a) => x(a).filter(Peg::is_const).map(|_| Peg::Bool(false)),
+
+        // A phi whose guard is known takes the value of the selected branch
+        Peg::Phi([c, t, e]) => match x(c)? {
+            Peg::Bool(true) => x(t),
+            Peg::Bool(false) => x(e),
+            Peg::Error => Some(Peg::Error),
+            _ => None,
+        },
+
+        // Loops. A sequence that starts at, and steps to, the same constant
+        // holds that constant at every iteration
+        Peg::Theta([a, b]) => x(a).filter(|a| Some(a) == x(b).as_ref()),
+        Peg::Eval([s, _]) => x(s),
+
+        // Calls to JDK methods on constants
+        Peg::PureCall([m, r, args]) => {
+            let receiver = if egraph[*r].nodes.contains(&Peg::Unit) { None } else { Some(x(r)?) };
+            let args = egraph[*args].nodes.iter().find_map(|node| match node {
+                Peg::Actuals(args) => args.iter().map(x).collect::<Option<Vec<Peg>>>(),
+                _ => None,
+            })?;
+            if receiver == Some(Peg::Error) || args.contains(&Peg::Error) {
+                return Some(Peg::Error);
+            }
+            purity::fold(&purity::call_name(egraph, *m, *r)?, receiver.as_ref(), &args)
+        }
 
         // Binary Ops
         Peg::BinAnd([a, b]) => Peg::bin_and(&x(a)?, &x(b)?),
@@ -514,41 +948,32 @@ fn eval(egraph: &EGraph, enode: &Peg) -> Option<Peg> {
 
         // Shifts
         Peg::SRShift([a, b]) => Peg::srshift(&x(a)?, &x(b)?),
+        Peg::URShift([a, b]) => Peg::urshift(&x(a)?, &x(b)?),
         Peg::LShift([a, b]) => Peg::lshift(&x(a)?, &x(b)?),
 
+        // Casts between primitive types apply the primitive conversion, and
+        // always succeed. Casts to reference types are left alone
+        Peg::Cast([a, t]) => match (x(a)?, primitive_type_name(egraph, *t)?) {
+            (Peg::Bool(b), None) => Some(Peg::Bool(b)),
+            (a, Some(to)) => Some(Peg::from(a.as_number()?.convert(to))),
+            _ => None,
+        },
+        Peg::CanCast([a, t]) => match (x(a)?, primitive_type_name(egraph, *t)?) {
+            (Peg::Bool(_), None) => Some(Peg::Bool(true)),
+            (a, Some(_)) => a.as_number().map(|_| Peg::Bool(true)),
+            _ => None,
+        },
+
         // Comparison
         Peg::Gte([a, b]) => Peg::ge(&x(a)?, &x(b)?),
         Peg::Gt([a, b]) => Peg::gt(&x(a)?, &x(b)?),
         Peg::Lte([a, b]) => Peg::le(&x(a)?, &x(b)?),
         Peg::Lt([a, b]) => Peg::lt(&x(a)?, &x(b)?),
 
-        Peg::Equ([a, b]) => {
-            // To check for equality,
-            let a = x(a)?;
-            let b = x(b)?;
-            return match (a, b) {
-                (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a == b)),
-                (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a == b)),
-                (Peg::Long(a), Peg::Num(b))  => Some(Peg::Bool(a == b)),
-                (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a == b)),
-                (Peg::Bool(a), Peg::Bool(b)) => Some(Peg::Bool(a == b)),
-                _ => Some(Peg::Bool(false))
-            }
-            
-        }
-        Peg::Neq([a, b]) => {
-            // To check for equality,
-            let a = x(a)?;
-            let b = x(b)?;
-            return match (a, b) {
-                (Peg::Num(a), Peg::Num(b)) => Some(Peg::Bool(a != b)),
-                (Peg::Num(a), Peg::Long(b)) => Some(Peg::Bool(a != b)),
-                (Peg::Long(a), Peg::Num(b))  => Some(Peg::Bool(a != b)),
-                (Peg::Long(a), Peg::Long(b)) => Some(Peg::Bool(a != b)),
-                (Peg::Bool(a), Peg::Bool(b)) => Some(Peg::Bool(a != b)),
-                _ => Some(Peg::Bool(false))
-            }
-        }
+        // Operands that `equal` can't compare (say, a string and a boolean,
+        // which can't occur in well-typed code) are left alone
+        Peg::Equ([a, b]) if comparable_references(egraph, *a, *b) => Peg::equal(&x(a)?, &x(b)?),
+        Peg::Neq([a, b]) if comparable_references(egraph, *a, *b) => Peg::nequal(&x(a)?, &x(b)?),
 
         _ => None,
     }
@@ -563,21 +988,61 @@ impl Analysis<Peg> for PegAnalysis {
     fn make(egraph: &EGraph, enode: &Peg) -> Self::Data {
         let _x = |i: &Id| egraph[*i].data.clone();
         match enode {
-            Peg::Var(_) => PegAnalysisData {
-                constant: None,
-                variable: Some(enode.clone()),
-            },
-            _ => PegAnalysisData {
-                constant: eval(egraph, enode),
-                variable: None,
-            },
+            Peg::Var([_, annotation]) => {
+                let numeric = declared_type(egraph, *annotation);
+                PegAnalysisData {
+                    constant: None,
+                    variable: Some(enode.clone()),
+                    range: numeric.and_then(Interval::of_type),
+                    numeric,
+                    pure: true,
+                }
+            }
+            _ => {
+                let constant = eval(egraph, enode);
+                let range = match &constant {
+                    Some(c) => Interval::of_constant(c),
+                    None => range_of(egraph, enode),
+                };
+                let pure = match &constant {
+                    Some(Peg::Error) => false,
+                    Some(_) => true,
+                    None => is_pure_node(egraph, enode),
+                };
+                let numeric = match &constant {
+                    Some(c) => c.as_number().map(JavaNumber::numeric_type),
+                    None => numeric_type_of(egraph, enode),
+                };
+                PegAnalysisData { constant, variable: None, range, numeric, pure }
+            }
         }
     }
 
     fn modify(egraph: &mut EGraph, id: Id) {
-        if let Some(c) = egraph[id].data.constant.clone() {
-            let const_id = egraph.add(c);
-            egraph.union(id, const_id);
+        // A computed string is a new object, so it isn't merged with the
+        // literal of the same contents
+        match egraph[id].data.constant.clone() {
+            Some(Peg::Str(_)) | None => (),
+            Some(c) => {
+                let const_id = egraph.add(c);
+                egraph.union(id, const_id);
+            }
+        }
+
+        // Even when the selected branch of a phi isn't a constant, the phi is
+        // equal to it
+        let taken_branches: Vec<Id> = egraph[id].nodes.iter()
+            .filter_map(|node| match node {
+                Peg::Phi([c, t, e]) => match egraph[*c].data.constant {
+                    Some(Peg::Bool(true)) => Some(*t),
+                    Some(Peg::Bool(false)) => Some(*e),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        for branch in taken_branches {
+            egraph.union(id, branch);
         }
     }
 }
@@ -601,3 +1066,171 @@ pub fn to_sexp_string(expr: &RecExpr<Peg>, i: usize) -> String {
         format!("({})", vec.join(" "))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(n: i32) -> Peg {
+        Peg::Num(JavaInt::from(n))
+    }
+
+    fn long(n: i64) -> Peg {
+        Peg::Long(JavaLong::from(n))
+    }
+
+    /// The constant the analysis folds `expr` to
+    fn constant(expr: &str) -> Option<Peg> {
+        let mut egraph = EGraph::default();
+        let root = egraph.add_expr(&expr.parse().unwrap());
+        egraph.rebuild();
+        egraph[root].data.constant.clone()
+    }
+
+    /// The analysis data of `expr`'s class
+    fn data(expr: &str) -> PegAnalysisData {
+        let mut egraph = EGraph::default();
+        let root = egraph.add_expr(&expr.parse().unwrap());
+        egraph.rebuild();
+        egraph[root].data.clone()
+    }
+
+    fn interval(lo: i64, hi: i64, is_long: bool) -> Option<Interval> {
+        Some(Interval { lo, hi, is_long })
+    }
+
+    #[test]
+    fn variables_take_the_range_of_their_declared_type() {
+        assert_eq!(data("(var x \"int\")").range, interval(i32::MIN as i64, i32::MAX as i64, false));
+        assert_eq!(data("(var x \"char\")").range, interval(0, 65535, false));
+        assert_eq!(data("(var x \"long\")").range, interval(i64::MIN, i64::MAX, true));
+        assert_eq!(data("(var x \"short\")").numeric, Some(NumericType::Short));
+        assert_eq!(data("(var x \"double\")").range, None);
+        assert_eq!(data("(var x \"double\")").numeric, Some(NumericType::Double));
+        assert_eq!(data("(var x \"java.lang.String\")").numeric, None);
+        assert_eq!(data("(var x nil)").numeric, None);
+    }
+
+    #[test]
+    fn ranges_and_types_follow_promotion() {
+        let sum = data("(+ (var x \"short\") (var y \"byte\"))");
+        assert_eq!(sum.range, interval(-32768 - 128, 32767 + 127, false));
+        assert_eq!(sum.numeric, Some(NumericType::Int));
+        // An int sum may overflow, so it has a type but no range
+        let sum = data("(+ (var x \"int\") (var y \"int\"))");
+        assert_eq!(sum.range, None);
+        assert_eq!(sum.numeric, Some(NumericType::Int));
+        assert_eq!(data("(* (var x \"int\") 2l)").numeric, Some(NumericType::Long));
+        assert_eq!(data("(+ 1 (var x \"float\"))").numeric, Some(NumericType::Float));
+        assert_eq!(data("(--- (var x \"char\"))").numeric, Some(NumericType::Int));
+        assert_eq!(data("(+ \"a\" (var x \"int\"))").numeric, None);
+        assert_eq!(data("1.5d").numeric, Some(NumericType::Double));
+    }
+
+    #[test]
+    fn casts_narrow_ranges() {
+        let cast = data("(cast (var d \"double\") (type-name \"int\"))");
+        assert_eq!(cast.range, interval(i32::MIN as i64, i32::MAX as i64, false));
+        assert_eq!(cast.numeric, Some(NumericType::Int));
+        let cast = data("(cast (var s \"short\") (type-name \"long\"))");
+        assert_eq!(cast.range, interval(-32768, 32767, true));
+        let cast = data("(cast (var i \"int\") (type-name \"byte\"))");
+        assert_eq!(cast.range, interval(-128, 127, false));
+        assert_eq!(data("(cast (var i \"int\") (type-name \"float\"))").range, None);
+        assert_eq!(data("(cast (var i \"int\") (type-name \"Object\"))").numeric, None);
+    }
+
+    #[test]
+    fn typed_variables_satisfy_range_guards() {
+        let mut egraph = EGraph::default();
+        let x = egraph.add_expr(&"(var x \"char\")".parse().unwrap());
+        let one = egraph.add(int(1));
+        let y = egraph.add_expr(&"(+ (var x \"char\") 1)".parse().unwrap());
+        let d = egraph.add_expr(&"(var d \"double\")".parse().unwrap());
+        egraph.rebuild();
+        let mut subst = Subst::default();
+        subst.insert("?a".parse().unwrap(), x);
+        subst.insert("?b".parse().unwrap(), one);
+        subst.insert("?y".parse().unwrap(), y);
+        subst.insert("?d".parse().unwrap(), d);
+        assert!(is_int("?a")(&mut egraph, x, &subst));
+        assert!(is_nonneg("?a")(&mut egraph, x, &subst));
+        assert!(is_nonzero("?y")(&mut egraph, y, &subst));
+        assert!(fits_without_overflow("*", "?a", "?b")(&mut egraph, x, &subst));
+        assert!(is_integral("?a")(&mut egraph, x, &subst));
+        assert!(!is_integral("?d")(&mut egraph, d, &subst));
+        assert!(!is_long("?d")(&mut egraph, d, &subst));
+    }
+
+    #[test]
+    fn incomparable_operands_are_not_folded() {
+        assert_eq!(constant("(== 1 true)"), None);
+        assert_eq!(constant("(!= true 1)"), None);
+        assert_eq!(constant("(== \"1\" 1)"), None);
+        assert_eq!(constant("(== null null)"), None);
+        assert_eq!(constant("(== 1 1l)"), Some(Peg::Bool(true)));
+        assert_eq!(constant("(!= true false)"), Some(Peg::Bool(true)));
+    }
+
+    #[test]
+    fn only_string_literals_compare_by_contents() {
+        assert_eq!(constant("(== \"ab\" \"ab\")"), Some(Peg::Bool(true)));
+        assert_eq!(constant("(!= \"ab\" \"b\")"), Some(Peg::Bool(true)));
+        assert_eq!(constant("(== (+ \"a\" \"b\") \"ab\")"), None);
+        assert_eq!(constant("(!= \"ab\" (+ \"a\" \"b\"))"), None);
+        assert_eq!(constant("(+ \"a\" 1)"), Some(Peg::Str(JavaString::from("a1"))));
+    }
+
+    #[test]
+    fn error_is_absorbing() {
+        assert_eq!(constant("(+ error 1)"), Some(Peg::Error));
+        assert_eq!(constant("(== 1 error)"), Some(Peg::Error));
+        assert_eq!(constant("(!= error error)"), Some(Peg::Error));
+        assert_eq!(constant("(! (/ 1 0))"), Some(Peg::Error));
+        assert_eq!(constant("(phi error 1 2)"), Some(Peg::Error));
+        assert_eq!(constant("(phi true 1 error)"), Some(int(1)));
+    }
+
+    #[test]
+    fn short_circuiting_operators_only_throw_when_they_evaluate_an_error() {
+        assert_eq!(constant("(&& false error)"), Some(Peg::Bool(false)));
+        assert_eq!(constant("(&& true error)"), Some(Peg::Error));
+        assert_eq!(constant("(&& error false)"), Some(Peg::Error));
+        assert_eq!(constant("(|| true error)"), Some(Peg::Bool(true)));
+        assert_eq!(constant("(|| false error)"), Some(Peg::Error));
+        assert_eq!(constant("(|| error true)"), Some(Peg::Error));
+    }
+
+    #[test]
+    fn shift_distances_compose_without_overflowing() {
+        let mut egraph = EGraph::default();
+        let big = egraph.add(long(i64::MAX));
+        let small = egraph.add(int(3));
+        let mut subst = Subst::default();
+        subst.insert("?b".parse().unwrap(), big);
+        subst.insert("?c".parse().unwrap(), small);
+        assert!(!shifts_compose("?b", "?c")(&mut egraph, big, &subst));
+        subst.insert("?b".parse().unwrap(), small);
+        assert!(shifts_compose("?b", "?c")(&mut egraph, big, &subst));
+    }
+
+    #[test]
+    fn integral_division_by_zero_is_an_error() {
+        assert_eq!(Peg::div(&int(1), &int(0)), Some(Peg::Error));
+        assert_eq!(Peg::rem(&int(1), &int(0)), Some(Peg::Error));
+        assert_eq!(Peg::div(&long(1), &int(0)), Some(Peg::Error));
+        assert_eq!(Peg::rem(&int(1), &long(0)), Some(Peg::Error));
+        assert_eq!(Peg::div(&int(i32::MIN), &int(-1)), Some(int(i32::MIN)));
+        assert_eq!(Peg::rem(&long(i64::MIN), &long(-1)), Some(long(0)));
+    }
+
+    #[test]
+    fn floating_division_by_zero_is_infinite() {
+        let double = |x: f64| Peg::Double(JavaDouble::from(x));
+        assert_eq!(Peg::div(&double(1.0), &int(0)), Some(double(f64::INFINITY)));
+        match Peg::rem(&double(1.0), &int(0)) {
+            Some(Peg::Double(x)) => assert!(x.value().is_nan()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -2,8 +2,11 @@ use std::str::FromStr;
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops;
+use egg::Symbol;
 use std::num::Wrapping;
 use std::convert::From;
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct JavaInt(i32);
@@ -11,6 +14,31 @@ pub struct JavaInt(i32);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct JavaLong(i64);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JavaShort(i16);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JavaByte(i8);
+
+/// A UTF-16 code unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JavaChar(u16);
+
+/// Floating point literals are compared, ordered and hashed by their bit
+/// patterns so that they can live in an e-graph: `NaN` is equal to itself and
+/// `0.0` is distinct from `-0.0`. Java's numeric comparisons (where neither
+/// holds) go through `value()` instead.
+#[derive(Clone, Copy, Debug)]
+pub struct JavaFloat(f32);
+
+#[derive(Clone, Copy, Debug)]
+pub struct JavaDouble(f64);
+
+/// A string literal, kept exactly as it was written in the source (escapes
+/// included, surrounding quotes removed)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JavaString(Symbol);
+
 impl From<i32> for JavaInt {
     fn from(x: i32) -> Self {
         JavaInt(x)
@@ -23,6 +51,113 @@ impl From<i64> for JavaLong {
     }
 }
 
+impl From<i16> for JavaShort {
+    fn from(x: i16) -> Self {
+        JavaShort(x)
+    }
+}
+
+impl From<i8> for JavaByte {
+    fn from(x: i8) -> Self {
+        JavaByte(x)
+    }
+}
+
+impl From<u16> for JavaChar {
+    fn from(x: u16) -> Self {
+        JavaChar(x)
+    }
+}
+
+impl From<f32> for JavaFloat {
+    fn from(x: f32) -> Self {
+        JavaFloat(x)
+    }
+}
+
+impl From<f64> for JavaDouble {
+    fn from(x: f64) -> Self {
+        JavaDouble(x)
+    }
+}
+
+impl From<&str> for JavaString {
+    fn from(s: &str) -> Self {
+        JavaString(Symbol::from(s))
+    }
+}
+
+impl JavaString {
+    /// The literal without its surrounding quotes
+    pub fn contents(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl JavaFloat {
+    pub fn value(self) -> f32 {
+        self.0
+    }
+}
+
+impl JavaDouble {
+    pub fn value(self) -> f64 {
+        self.0
+    }
+}
+
+impl PartialEq for JavaFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for JavaFloat {}
+
+impl PartialOrd for JavaFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JavaFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.to_bits().cmp(&other.0.to_bits())
+    }
+}
+
+impl Hash for JavaFloat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
+impl PartialEq for JavaDouble {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for JavaDouble {}
+
+impl PartialOrd for JavaDouble {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JavaDouble {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.to_bits().cmp(&other.0.to_bits())
+    }
+}
+
+impl Hash for JavaDouble {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
 pub trait IsZero {
     fn is_zero(&self) -> bool;
 }
@@ -39,6 +174,28 @@ impl IsZero for JavaInt {
     }
 }
 
+/// Java's unsigned right shift `>>>`, which has no Rust operator
+pub trait UnsignedShr<Rhs> {
+    type Output;
+    fn unsigned_shr(self, rhs: Rhs) -> Self::Output;
+}
+
+pub trait IsNegative {
+    fn is_negative(&self) -> bool;
+}
+
+impl IsNegative for JavaLong {
+    fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+}
+
+impl IsNegative for JavaInt {
+    fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+}
+
 impl FromStr for JavaLong {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -68,6 +225,170 @@ impl FromStr for JavaInt {
     }
 }
 
+impl FromStr for JavaShort {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix(|c| c == 's' || c == 'S') {
+            None => Err("JavaShort literals must end with `s` or `S`".to_string()),
+            Some(s) => s.parse::<i16>()
+                .map(JavaShort)
+                .map_err(|_| "Error parsing as i16".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JavaShort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0)
+    }
+}
+
+impl FromStr for JavaByte {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix(|c| c == 'b' || c == 'B') {
+            None => Err("JavaByte literals must end with `b` or `B`".to_string()),
+            Some(s) => s.parse::<i8>()
+                .map(JavaByte)
+                .map_err(|_| "Error parsing as i8".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JavaByte {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}b", self.0)
+    }
+}
+
+/// Char literals are written as in Java source: `'a'`, `'\n'` or `'\u00e9'`
+impl FromStr for JavaChar {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = s.strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+            .ok_or_else(|| "JavaChar literals must be single quoted".to_string())?;
+        let unit = match body {
+            "\\b" => 0x08,
+            "\\t" => 0x09,
+            "\\n" => 0x0a,
+            "\\f" => 0x0c,
+            "\\r" => 0x0d,
+            "\\\"" => 0x22,
+            "\\'" => 0x27,
+            "\\\\" => 0x5c,
+            _ => match body.strip_prefix("\\u") {
+                Some(hex) if hex.len() == 4 => u16::from_str_radix(hex, 16)
+                    .map_err(|_| format!("Bad unicode escape in char literal {}", s))?,
+                Some(_) => return Err(format!("Bad unicode escape in char literal {}", s)),
+                None => {
+                    let mut chars = body.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c != '\\' && (c as u32) <= 0xffff => c as u32 as u16,
+                        _ => return Err(format!("Bad char literal {}", s)),
+                    }
+                }
+            },
+        };
+        Ok(JavaChar(unit))
+    }
+}
+
+impl fmt::Display for JavaChar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0x27 => write!(f, "'\\''"),
+            0x5c => write!(f, "'\\\\'"),
+            0x20..=0x7e => write!(f, "'{}'", self.0 as u8 as char),
+            unit => write!(f, "'\\u{:04x}'", unit),
+        }
+    }
+}
+
+/// Floating point literals always carry their suffix, and use Java's spelling
+/// for the special values (`NaNf`, `Infinityf`, `-Infinityf`)
+impl FromStr for JavaFloat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix(|c| c == 'f' || c == 'F') {
+            None => Err("JavaFloat literals must end with `f` or `F`".to_string()),
+            Some(s) => parse_floating(s, f32::NAN, f32::INFINITY)
+                .map(JavaFloat)
+                .ok_or_else(|| "Error parsing as f32".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JavaFloat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_floating(f, self.0 as f64, &self.0.to_string(), 'f')
+    }
+}
+
+/// Double literals may drop their suffix when they have a decimal point or an
+/// exponent, as in Java source
+impl FromStr for JavaDouble {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = match s.strip_suffix(|c| c == 'd' || c == 'D') {
+            Some(body) => body,
+            None if s.contains(|c| c == '.' || c == 'e' || c == 'E') => s,
+            None => return Err("JavaDouble literals must end with `d` or `D`".to_string()),
+        };
+        parse_floating(body, f64::NAN, f64::INFINITY)
+            .map(JavaDouble)
+            .ok_or_else(|| "Error parsing as f64".to_string())
+    }
+}
+
+impl fmt::Display for JavaDouble {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_floating(f, self.0, &self.0.to_string(), 'd')
+    }
+}
+
+// Parses straight to the target precision: going through f64 for a float
+// literal could round twice
+fn parse_floating<T: FromStr + ops::Neg<Output = T>>(s: &str, nan: T, infinity: T) -> Option<T> {
+    match s {
+        "NaN" => Some(nan),
+        "Infinity" => Some(infinity),
+        "-Infinity" => Some(-infinity),
+        // Rust also accepts `inf` and `nan`, which could be identifiers
+        _ if s.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '+') =>
+            s.parse::<T>().ok(),
+        _ => None,
+    }
+}
+
+fn write_floating(f: &mut Formatter<'_>, x: f64, finite: &str, suffix: char) -> fmt::Result {
+    if x.is_nan() {
+        write!(f, "NaN{}", suffix)
+    } else if x == f64::INFINITY {
+        write!(f, "Infinity{}", suffix)
+    } else if x == f64::NEG_INFINITY {
+        write!(f, "-Infinity{}", suffix)
+    } else {
+        write!(f, "{}{}", finite, suffix)
+    }
+}
+
+impl FromStr for JavaString {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(body) => Ok(JavaString::from(body)),
+            None => Err("JavaString literals must be double quoted".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JavaString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
 impl PartialEq<JavaInt> for JavaLong {
     fn eq(&self, other: &JavaInt) -> bool {
         other.0 as i64 == self.0
@@ -90,108 +411,128 @@ impl JavaInt {
     pub fn promote_to_java_long(&self) -> JavaLong {
         JavaLong(self.0 as i64)
     }
+
+    pub fn value(self) -> i32 {
+        self.0
+    }
+}
+
+impl JavaLong {
+    pub fn value(self) -> i64 {
+        self.0
+    }
 }
 
 
 /*   Operators   */
+//
+// All operators follow JLS §15: arithmetic wraps in two's complement (so
+// `MIN / -1 == MIN` and `MIN % -1 == 0`), the remainder takes the sign of the
+// dividend, and shift distances are masked to their low 5 (int) or 6 (long)
+// bits. Binary numeric promotion turns mixed int/long arithmetic into long
+// arithmetic, but the type of a shift is that of its left operand alone.
+//
+// Division and remainder by zero throw `ArithmeticException` in Java; here they
+// give `None`.
+
 /*      ~~~ Ops for JavaLong ~~~      */
 
 
 impl ops::Add<JavaLong> for JavaLong {
     type Output = JavaLong;
     fn add(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(Wrapping(self.0 + rhs.0).0)
+        JavaLong((Wrapping(self.0) + Wrapping(rhs.0)).0)
     }
 }
 
 impl ops::Add<JavaInt> for JavaLong {
     type Output = JavaLong;
     fn add(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(Wrapping(self.0 + (rhs.0 as i64)).0)
+        self + rhs.promote_to_java_long()
     }
 }
 
 impl ops::Sub<JavaLong> for JavaLong {
     type Output = JavaLong;
     fn sub(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(Wrapping(self.0 - rhs.0).0)
+        JavaLong((Wrapping(self.0) - Wrapping(rhs.0)).0)
     }
 }
 
 impl ops::Sub<JavaInt> for JavaLong {
     type Output = JavaLong;
     fn sub(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(Wrapping(self.0 - (rhs.0 as i64)).0)
+        self - rhs.promote_to_java_long()
     }
 }
 
 impl ops::Mul<JavaLong> for JavaLong {
     type Output = JavaLong;
     fn mul(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(Wrapping(self.0 * rhs.0).0)
+        JavaLong((Wrapping(self.0) * Wrapping(rhs.0)).0)
     }
 }
 
 impl ops::Mul<JavaInt> for JavaLong {
     type Output = JavaLong;
     fn mul(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(Wrapping(self.0 * (rhs.0 as i64)).0)
+        self * rhs.promote_to_java_long()
     }
 }
 
 impl ops::Div<JavaLong> for JavaLong {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn div(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(Wrapping(self.0 / rhs.0).0)
+        if rhs.is_zero() { None } else { Some(JavaLong(self.0.wrapping_div(rhs.0))) }
     }
 }
 
 impl ops::Div<JavaInt> for JavaLong {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn div(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(Wrapping(self.0 / (rhs.0 as i64)).0)
+        self / rhs.promote_to_java_long()
     }
 }
 
 impl ops::Rem<JavaLong> for JavaLong {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn rem(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(Wrapping(self.0 % rhs.0).0)
+        if rhs.is_zero() { None } else { Some(JavaLong(self.0.wrapping_rem(rhs.0))) }
     }
 }
 
 impl ops::Rem<JavaInt> for JavaLong {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn rem(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(Wrapping(self.0 % (rhs.0 as i64)).0)
+        self % rhs.promote_to_java_long()
     }
 }
 
 impl ops::Shl<JavaLong> for JavaLong {
     type Output = JavaLong;
     fn shl(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(self.0 << rhs.0)
+        JavaLong(self.0 << (rhs.0 & 0x3f))
     }
 }
 
 impl ops::Shl<JavaInt> for JavaLong {
     type Output = JavaLong;
     fn shl(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(self.0 << (rhs.0 as i64))
+        JavaLong(self.0 << (rhs.0 & 0x3f))
     }
 }
 
 impl ops::Shr<JavaLong> for JavaLong {
     type Output = JavaLong;
     fn shr(self, rhs: JavaLong) -> Self::Output {
-        JavaLong(self.0 >> rhs.0)
+        JavaLong(self.0 >> (rhs.0 & 0x3f))
     }
 }
 
 impl ops::Shr<JavaInt> for JavaLong {
     type Output = JavaLong;
     fn shr(self, rhs: JavaInt) -> Self::Output {
-        JavaLong(self.0 >> (rhs.0 as i64))
+        JavaLong(self.0 >> (rhs.0 & 0x3f))
     }
 }
 
@@ -237,10 +578,31 @@ impl ops::BitXor<JavaInt> for JavaLong {
     }
 }
 
+impl UnsignedShr<JavaLong> for JavaLong {
+    type Output = JavaLong;
+    fn unsigned_shr(self, rhs: JavaLong) -> Self::Output {
+        JavaLong(((self.0 as u64) >> (rhs.0 & 0x3f)) as i64)
+    }
+}
+
+impl UnsignedShr<JavaInt> for JavaLong {
+    type Output = JavaLong;
+    fn unsigned_shr(self, rhs: JavaInt) -> Self::Output {
+        JavaLong(((self.0 as u64) >> (rhs.0 & 0x3f)) as i64)
+    }
+}
+
 impl ops::Neg for JavaLong {
     type Output = JavaLong;
     fn neg(self) -> Self::Output {
-        JavaLong(- self.0)
+        JavaLong((-Wrapping(self.0)).0)
+    }
+}
+
+impl ops::Not for JavaLong {
+    type Output = JavaLong;
+    fn not(self) -> Self::Output {
+        JavaLong(!self.0)
     }
 }
 
@@ -257,7 +619,7 @@ impl ops::Add<JavaInt> for JavaInt {
 impl ops::Add<JavaLong> for JavaInt {
     type Output = JavaLong;
     fn add(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((Wrapping(self.0 as i64) + Wrapping(rhs.0)).0)
+        self.promote_to_java_long() + rhs
     }
 }
 
@@ -271,7 +633,7 @@ impl ops::Sub<JavaInt> for JavaInt {
 impl ops::Sub<JavaLong> for JavaInt {
     type Output = JavaLong;
     fn sub(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((Wrapping(self.0 as i64) - Wrapping(rhs.0)).0)
+        self.promote_to_java_long() - rhs
     }
 }
 
@@ -285,63 +647,63 @@ impl ops::Mul<JavaInt> for JavaInt {
 impl ops::Mul<JavaLong> for JavaInt {
     type Output = JavaLong;
     fn mul(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((Wrapping(self.0 as i64) * Wrapping(rhs.0)).0)
+        self.promote_to_java_long() * rhs
     }
 }
 
 impl ops::Div<JavaInt> for JavaInt {
-    type Output = JavaInt;
+    type Output = Option<JavaInt>;
     fn div(self, rhs: JavaInt) -> Self::Output {
-        JavaInt((Wrapping(self.0) / Wrapping(rhs.0)).0)
+        if rhs.is_zero() { None } else { Some(JavaInt(self.0.wrapping_div(rhs.0))) }
     }
 }
 
 impl ops::Div<JavaLong> for JavaInt {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn div(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((Wrapping(self.0 as i64) / Wrapping(rhs.0)).0)
+        self.promote_to_java_long() / rhs
     }
 }
 
 impl ops::Rem<JavaInt> for JavaInt {
-    type Output = JavaInt;
+    type Output = Option<JavaInt>;
     fn rem(self, rhs: JavaInt) -> Self::Output {
-        JavaInt(self.0 % rhs.0)
+        if rhs.is_zero() { None } else { Some(JavaInt(self.0.wrapping_rem(rhs.0))) }
     }
 }
 
 impl ops::Rem<JavaLong> for JavaInt {
-    type Output = JavaLong;
+    type Output = Option<JavaLong>;
     fn rem(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((self.0 as i64) % rhs.0)
+        self.promote_to_java_long() % rhs
     }
 }
 
 impl ops::Shl<JavaInt> for JavaInt {
     type Output = JavaInt;
     fn shl(self, rhs: JavaInt) -> Self::Output {
-        JavaInt(self.0 << rhs.0)
+        JavaInt(self.0 << (rhs.0 & 0x1f))
     }
 }
 
 impl ops::Shl<JavaLong> for JavaInt {
-    type Output = JavaLong;
+    type Output = JavaInt;
     fn shl(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((self.0 as i64) << rhs.0)
+        JavaInt(self.0 << (rhs.0 & 0x1f))
     }
 }
 
 impl ops::Shr<JavaInt> for JavaInt {
     type Output = JavaInt;
     fn shr(self, rhs: JavaInt) -> Self::Output {
-        JavaInt(self.0 >> rhs.0)
+        JavaInt(self.0 >> (rhs.0 & 0x1f))
     }
 }
 
 impl ops::Shr<JavaLong> for JavaInt {
-    type Output = JavaLong;
+    type Output = JavaInt;
     fn shr(self, rhs: JavaLong) -> Self::Output {
-        JavaLong((self.0 as i64) >> rhs.0)
+        JavaInt(self.0 >> (rhs.0 & 0x1f))
     }
 }
 
@@ -387,9 +749,544 @@ impl ops::BitXor<JavaLong> for JavaInt {
     }
 }
 
+impl UnsignedShr<JavaInt> for JavaInt {
+    type Output = JavaInt;
+    fn unsigned_shr(self, rhs: JavaInt) -> Self::Output {
+        JavaInt(((self.0 as u32) >> (rhs.0 & 0x1f)) as i32)
+    }
+}
+
+impl UnsignedShr<JavaLong> for JavaInt {
+    type Output = JavaInt;
+    fn unsigned_shr(self, rhs: JavaLong) -> Self::Output {
+        JavaInt(((self.0 as u32) >> (rhs.0 & 0x1f)) as i32)
+    }
+}
+
 impl ops::Neg for JavaInt {
     type Output = JavaInt;
     fn neg(self) -> Self::Output {
-        JavaInt(- self.0)
+        JavaInt((-Wrapping(self.0)).0)
+    }
+}
+
+impl ops::Not for JavaInt {
+    type Output = JavaInt;
+    fn not(self) -> Self::Output {
+        JavaInt(!self.0)
+    }
+}
+
+
+/*      ~~~ Ops for JavaFloat and JavaDouble ~~~      */
+//
+// IEEE 754 arithmetic, as in Java: division by zero gives an infinity or NaN
+// rather than throwing, and `%` truncates like C's `fmod`.
+
+impl ops::Add<JavaFloat> for JavaFloat {
+    type Output = JavaFloat;
+    fn add(self, rhs: JavaFloat) -> Self::Output {
+        JavaFloat(self.0 + rhs.0)
+    }
+}
+
+impl ops::Sub<JavaFloat> for JavaFloat {
+    type Output = JavaFloat;
+    fn sub(self, rhs: JavaFloat) -> Self::Output {
+        JavaFloat(self.0 - rhs.0)
+    }
+}
+
+impl ops::Mul<JavaFloat> for JavaFloat {
+    type Output = JavaFloat;
+    fn mul(self, rhs: JavaFloat) -> Self::Output {
+        JavaFloat(self.0 * rhs.0)
+    }
+}
+
+impl ops::Div<JavaFloat> for JavaFloat {
+    type Output = JavaFloat;
+    fn div(self, rhs: JavaFloat) -> Self::Output {
+        JavaFloat(self.0 / rhs.0)
+    }
+}
+
+impl ops::Rem<JavaFloat> for JavaFloat {
+    type Output = JavaFloat;
+    fn rem(self, rhs: JavaFloat) -> Self::Output {
+        JavaFloat(self.0 % rhs.0)
+    }
+}
+
+impl ops::Neg for JavaFloat {
+    type Output = JavaFloat;
+    fn neg(self) -> Self::Output {
+        JavaFloat(-self.0)
+    }
+}
+
+impl ops::Add<JavaDouble> for JavaDouble {
+    type Output = JavaDouble;
+    fn add(self, rhs: JavaDouble) -> Self::Output {
+        JavaDouble(self.0 + rhs.0)
+    }
+}
+
+impl ops::Sub<JavaDouble> for JavaDouble {
+    type Output = JavaDouble;
+    fn sub(self, rhs: JavaDouble) -> Self::Output {
+        JavaDouble(self.0 - rhs.0)
+    }
+}
+
+impl ops::Mul<JavaDouble> for JavaDouble {
+    type Output = JavaDouble;
+    fn mul(self, rhs: JavaDouble) -> Self::Output {
+        JavaDouble(self.0 * rhs.0)
+    }
+}
+
+impl ops::Div<JavaDouble> for JavaDouble {
+    type Output = JavaDouble;
+    fn div(self, rhs: JavaDouble) -> Self::Output {
+        JavaDouble(self.0 / rhs.0)
+    }
+}
+
+impl ops::Rem<JavaDouble> for JavaDouble {
+    type Output = JavaDouble;
+    fn rem(self, rhs: JavaDouble) -> Self::Output {
+        JavaDouble(self.0 % rhs.0)
+    }
+}
+
+impl ops::Neg for JavaDouble {
+    type Output = JavaDouble;
+    fn neg(self) -> Self::Output {
+        JavaDouble(-self.0)
+    }
+}
+
+
+/*   Conversions and Promotion   */
+
+/// Java's primitive numeric types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericType {
+    Byte,
+    Short,
+    Char,
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl NumericType {
+    /// The type named by a Java type name, like the `"long"` in
+    /// `(type-name "long")`
+    pub fn from_name(name: &str) -> Option<NumericType> {
+        match name {
+            "byte" => Some(NumericType::Byte),
+            "short" => Some(NumericType::Short),
+            "char" => Some(NumericType::Char),
+            "int" => Some(NumericType::Int),
+            "long" => Some(NumericType::Long),
+            "float" => Some(NumericType::Float),
+            "double" => Some(NumericType::Double),
+            _ => None,
+        }
+    }
+
+    /// Whether the type's values are integers, as `char`'s are
+    pub fn is_integral(self) -> bool {
+        !matches!(self, NumericType::Float | NumericType::Double)
+    }
+
+    /// The type after unary numeric promotion (JLS §5.6.1)
+    pub fn promoted(self) -> NumericType {
+        match self {
+            NumericType::Byte | NumericType::Short | NumericType::Char => NumericType::Int,
+            t => t,
+        }
+    }
+
+    /// The type both operands of a binary operator are promoted to (JLS §5.6.2)
+    pub fn binary(a: NumericType, b: NumericType) -> NumericType {
+        use NumericType::*;
+        match (a.promoted(), b.promoted()) {
+            (Double, _) | (_, Double) => Double,
+            (Float, _) | (_, Float) => Float,
+            (Long, _) | (_, Long) => Long,
+            _ => Int,
+        }
+    }
+}
+
+/// A value of any of Java's primitive numeric types
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JavaNumber {
+    Byte(JavaByte),
+    Short(JavaShort),
+    Char(JavaChar),
+    Int(JavaInt),
+    Long(JavaLong),
+    Float(JavaFloat),
+    Double(JavaDouble),
+}
+
+/// The operands of a binary operator after binary numeric promotion
+/// (JLS §5.6.2): both have the same type, which is at least `int`
+pub enum Promoted {
+    Int(JavaInt, JavaInt),
+    Long(JavaLong, JavaLong),
+    Float(JavaFloat, JavaFloat),
+    Double(JavaDouble, JavaDouble),
+}
+
+impl JavaNumber {
+    pub fn numeric_type(self) -> NumericType {
+        match self {
+            JavaNumber::Byte(_) => NumericType::Byte,
+            JavaNumber::Short(_) => NumericType::Short,
+            JavaNumber::Char(_) => NumericType::Char,
+            JavaNumber::Int(_) => NumericType::Int,
+            JavaNumber::Long(_) => NumericType::Long,
+            JavaNumber::Float(_) => NumericType::Float,
+            JavaNumber::Double(_) => NumericType::Double,
+        }
+    }
+
+    /// Integral values are exact as an i64, and floats are exact as an f64
+    fn widest(self) -> Result<i64, f64> {
+        match self {
+            JavaNumber::Byte(n) => Ok(n.0 as i64),
+            JavaNumber::Short(n) => Ok(n.0 as i64),
+            JavaNumber::Char(n) => Ok(n.0 as i64),
+            JavaNumber::Int(n) => Ok(n.0 as i64),
+            JavaNumber::Long(n) => Ok(n.0),
+            JavaNumber::Float(x) => Err(x.0 as f64),
+            JavaNumber::Double(x) => Err(x.0),
+        }
+    }
+
+    // Narrowing keeps the low-order bits of an integral value (JLS §5.1.3).
+    // Rust's float-to-int `as` casts saturate and send NaN to 0, just as Java
+    // does when converting to int or long; conversions to the smaller integral
+    // types go through int first.
+
+    pub fn to_java_byte(self) -> JavaByte {
+        JavaByte(self.to_java_int().0 as i8)
+    }
+
+    pub fn to_java_short(self) -> JavaShort {
+        JavaShort(self.to_java_int().0 as i16)
+    }
+
+    pub fn to_java_char(self) -> JavaChar {
+        JavaChar(self.to_java_int().0 as u16)
+    }
+
+    pub fn to_java_int(self) -> JavaInt {
+        match self.widest() {
+            Ok(n) => JavaInt(n as i32),
+            Err(x) => JavaInt(x as i32),
+        }
+    }
+
+    pub fn to_java_long(self) -> JavaLong {
+        match self.widest() {
+            Ok(n) => JavaLong(n),
+            Err(x) => JavaLong(x as i64),
+        }
+    }
+
+    pub fn to_java_float(self) -> JavaFloat {
+        match self.widest() {
+            Ok(n) => JavaFloat(n as f32),
+            Err(x) => JavaFloat(x as f32),
+        }
+    }
+
+    pub fn to_java_double(self) -> JavaDouble {
+        match self.widest() {
+            Ok(n) => JavaDouble(n as f64),
+            Err(x) => JavaDouble(x),
+        }
+    }
+
+    /// Apply the widening or narrowing primitive conversion to `to`
+    pub fn convert(self, to: NumericType) -> JavaNumber {
+        match to {
+            NumericType::Byte => JavaNumber::Byte(self.to_java_byte()),
+            NumericType::Short => JavaNumber::Short(self.to_java_short()),
+            NumericType::Char => JavaNumber::Char(self.to_java_char()),
+            NumericType::Int => JavaNumber::Int(self.to_java_int()),
+            NumericType::Long => JavaNumber::Long(self.to_java_long()),
+            NumericType::Float => JavaNumber::Float(self.to_java_float()),
+            NumericType::Double => JavaNumber::Double(self.to_java_double()),
+        }
+    }
+
+    /// Unary numeric promotion (JLS §5.6.1): byte, short and char become int
+    pub fn unary_promotion(self) -> JavaNumber {
+        match self {
+            JavaNumber::Byte(_) | JavaNumber::Short(_) | JavaNumber::Char(_) =>
+                JavaNumber::Int(self.to_java_int()),
+            _ => self,
+        }
+    }
+
+    pub fn binary_promotion(a: JavaNumber, b: JavaNumber) -> Promoted {
+        use NumericType::*;
+        match (a.numeric_type(), b.numeric_type()) {
+            (Double, _) | (_, Double) => Promoted::Double(a.to_java_double(), b.to_java_double()),
+            (Float, _) | (_, Float) => Promoted::Float(a.to_java_float(), b.to_java_float()),
+            (Long, _) | (_, Long) => Promoted::Long(a.to_java_long(), b.to_java_long()),
+            _ => Promoted::Int(a.to_java_int(), b.to_java_int()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Boundary values, shift distances around the masks, and a spread of
+    /// pseudo-random values
+    fn longs() -> Vec<i64> {
+        let mut values = vec![
+            0, 1, -1, 2, -2, 7, -7, 31, 32, 33, 63, 64, 65, -31, -32, -33, -64, -65,
+            i32::MIN as i64, i32::MAX as i64, i32::MIN as i64 - 1, i32::MAX as i64 + 1,
+            i64::MIN, i64::MAX, i64::MIN + 1, i64::MAX - 1,
+        ];
+        let mut x: u64 = 0x9e37_79b9_7f4a_7c15;
+        for _ in 0..40 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            values.push(x as i64);
+        }
+        values
+    }
+
+    fn ints() -> Vec<i32> {
+        let mut values: Vec<i32> = longs().into_iter().map(|n| n as i32).collect();
+        values.extend(&[i32::MIN + 1, i32::MAX - 1]);
+        values
+    }
+
+    fn pairs<T: Copy>(values: &[T]) -> Vec<(T, T)> {
+        values.iter().flat_map(|&a| values.iter().map(move |&b| (a, b))).collect()
+    }
+
+    // References computed in i128, which holds every intermediate result,
+    // then truncated to the operand type as Java does
+
+    fn int_ref(op: &str, a: i32, b: i32) -> Option<i32> {
+        let (x, y) = (a as i128, b as i128);
+        let shift = (b & 0x1f) as u32;
+        Some(match op {
+            "+" => (x + y) as i32,
+            "-" => (x - y) as i32,
+            "*" => (x * y) as i32,
+            "/" if y == 0 => return None,
+            "/" => (x / y) as i32,
+            "%" if y == 0 => return None,
+            "%" => (x % y) as i32,
+            "<<" => ((x as u32 as u64) << shift) as u32 as i32,
+            ">>" => (x >> shift) as i32,
+            ">>>" => ((a as u32) >> shift) as i32,
+            _ => unreachable!(),
+        })
+    }
+
+    fn long_ref(op: &str, a: i64, b: i64) -> Option<i64> {
+        let (x, y) = (a as i128, b as i128);
+        let shift = (b & 0x3f) as u32;
+        Some(match op {
+            "+" => (x + y) as i64,
+            "-" => (x - y) as i64,
+            "*" => x.wrapping_mul(y) as i64,
+            "/" if y == 0 => return None,
+            "/" => (x / y) as i64,
+            "%" if y == 0 => return None,
+            "%" => (x % y) as i64,
+            "<<" => ((a as u64 as u128) << shift) as u64 as i64,
+            ">>" => (x >> shift) as i64,
+            ">>>" => ((a as u64) >> shift) as i64,
+            _ => unreachable!(),
+        })
+    }
+
+    fn int_op(op: &str, a: JavaInt, b: JavaInt) -> Option<JavaInt> {
+        Some(match op {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => return a / b,
+            "%" => return a % b,
+            "<<" => a << b,
+            ">>" => a >> b,
+            ">>>" => a.unsigned_shr(b),
+            _ => unreachable!(),
+        })
+    }
+
+    fn long_op(op: &str, a: JavaLong, b: JavaLong) -> Option<JavaLong> {
+        Some(match op {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => return a / b,
+            "%" => return a % b,
+            "<<" => a << b,
+            ">>" => a >> b,
+            ">>>" => a.unsigned_shr(b),
+            _ => unreachable!(),
+        })
+    }
+
+    const OPS: &[&str] = &["+", "-", "*", "/", "%", "<<", ">>", ">>>"];
+
+    #[test]
+    fn int_ops_match_reference() {
+        for op in OPS {
+            for (a, b) in pairs(&ints()) {
+                let expected = int_ref(op, a, b).map(JavaInt);
+                assert_eq!(int_op(op, JavaInt(a), JavaInt(b)), expected, "{} {} {}", a, op, b);
+            }
+        }
+    }
+
+    #[test]
+    fn long_ops_match_reference() {
+        for op in OPS {
+            for (a, b) in pairs(&longs()) {
+                let expected = long_ref(op, a, b).map(JavaLong);
+                assert_eq!(long_op(op, JavaLong(a), JavaLong(b)), expected, "{} {} {}", a, op, b);
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_arithmetic_promotes_to_long() {
+        for (a, b) in pairs(&longs()) {
+            let (i, l) = (JavaInt(a as i32), JavaLong(b));
+            let x = a as i32 as i64;
+            assert_eq!(i + l, JavaLong(x.wrapping_add(b)));
+            assert_eq!(l - i, JavaLong(b.wrapping_sub(x)));
+            assert_eq!(i * l, JavaLong(x.wrapping_mul(b)));
+            assert_eq!(i / l, long_ref("/", x, b).map(JavaLong));
+            assert_eq!(l % i, long_ref("%", b, x).map(JavaLong));
+        }
+    }
+
+    #[test]
+    fn shifts_take_the_type_of_their_left_operand() {
+        for (a, b) in pairs(&longs()) {
+            assert_eq!(JavaInt(a as i32) << JavaLong(b), JavaInt(int_ref("<<", a as i32, b as i32).unwrap()));
+            assert_eq!(JavaLong(a) >> JavaInt(b as i32), JavaLong(long_ref(">>", a, b as i32 as i64).unwrap()));
+            assert_eq!(JavaLong(a).unsigned_shr(JavaInt(b as i32)), JavaLong(long_ref(">>>", a, b as i32 as i64).unwrap()));
+        }
+    }
+
+    #[test]
+    fn overflowing_division_wraps() {
+        assert_eq!(JavaInt(i32::MIN) / JavaInt(-1), Some(JavaInt(i32::MIN)));
+        assert_eq!(JavaInt(i32::MIN) % JavaInt(-1), Some(JavaInt(0)));
+        assert_eq!(JavaLong(i64::MIN) / JavaLong(-1), Some(JavaLong(i64::MIN)));
+        assert_eq!(JavaLong(i64::MIN) % JavaLong(-1), Some(JavaLong(0)));
+        // Promoted to long, `int` MIN / -1 doesn't overflow
+        assert_eq!(JavaInt(i32::MIN) / JavaLong(-1), Some(JavaLong(-(i32::MIN as i64))));
+    }
+
+    #[test]
+    fn division_by_zero_has_no_value() {
+        assert_eq!(JavaInt(1) / JavaInt(0), None);
+        assert_eq!(JavaInt(0) % JavaInt(0), None);
+        assert_eq!(JavaLong(1) / JavaLong(0), None);
+        assert_eq!(JavaLong(1) % JavaInt(0), None);
+        assert_eq!(JavaInt(1) / JavaLong(0), None);
+    }
+
+    #[test]
+    fn remainder_takes_the_sign_of_the_dividend() {
+        assert_eq!(JavaInt(-7) % JavaInt(2), Some(JavaInt(-1)));
+        assert_eq!(JavaInt(7) % JavaInt(-2), Some(JavaInt(1)));
+        assert_eq!(JavaInt(-7) / JavaInt(2), Some(JavaInt(-3)));
+    }
+
+    #[test]
+    fn shift_distances_are_masked() {
+        assert_eq!(JavaInt(1) << JavaInt(32), JavaInt(1));
+        assert_eq!(JavaInt(1) << JavaInt(33), JavaInt(2));
+        assert_eq!(JavaInt(1) << JavaInt(-1), JavaInt(i32::MIN));
+        assert_eq!(JavaLong(1) << JavaLong(64), JavaLong(1));
+        assert_eq!(JavaLong(1) << JavaInt(63), JavaLong(i64::MIN));
+        assert_eq!(JavaInt(-1).unsigned_shr(JavaInt(28)), JavaInt(15));
+        assert_eq!(JavaLong(-1).unsigned_shr(JavaLong(60)), JavaLong(15));
+    }
+
+    /// `literal` parsed and printed again, which must parse to the same value
+    fn reprint<T>(literal: &str) -> String
+    where
+        T: FromStr + fmt::Display + PartialEq + fmt::Debug,
+        T::Err: fmt::Debug,
+    {
+        let value: T = literal.parse().unwrap();
+        let printed = value.to_string();
+        assert_eq!(printed.parse::<T>().unwrap(), value, "{} printed as {}", literal, printed);
+        printed
+    }
+
+    // The spellings are those the serializer prints
+    #[test]
+    fn char_literals_round_trip() {
+        assert_eq!(reprint::<JavaChar>("'a'"), "'a'");
+        assert_eq!(reprint::<JavaChar>("'\"'"), "'\"'");
+        assert_eq!(reprint::<JavaChar>("'\\''"), "'\\''");
+        assert_eq!(reprint::<JavaChar>("'\\\\'"), "'\\\\'");
+        assert_eq!(reprint::<JavaChar>("'\\u000a'"), "'\\u000a'");
+        assert_eq!(reprint::<JavaChar>("'\\n'"), "'\\u000a'");
+        assert_eq!(reprint::<JavaChar>("'\\u00e9'"), "'\\u00e9'");
+        assert!("'ab'".parse::<JavaChar>().is_err());
+        assert!("'\\u00e'".parse::<JavaChar>().is_err());
+    }
+
+    #[test]
+    fn floating_literals_round_trip() {
+        assert_eq!(reprint::<JavaFloat>("1.5f"), "1.5f");
+        assert_eq!(reprint::<JavaFloat>("NaNf"), "NaNf");
+        assert_eq!(reprint::<JavaFloat>("Infinityf"), "Infinityf");
+        assert_eq!(reprint::<JavaFloat>("-Infinityf"), "-Infinityf");
+        reprint::<JavaFloat>("1.0E10f");
+        reprint::<JavaFloat>("-0.0f");
+        assert_eq!(reprint::<JavaDouble>("1.5d"), "1.5d");
+        assert_eq!(reprint::<JavaDouble>("NaNd"), "NaNd");
+        assert_eq!(reprint::<JavaDouble>("-Infinityd"), "-Infinityd");
+        reprint::<JavaDouble>("1.0E-5d");
+        reprint::<JavaDouble>("4.9E-324d");
+        assert!("1.5".parse::<JavaFloat>().is_err());
+        assert!("inff".parse::<JavaFloat>().is_err());
+        assert!("1d".parse::<JavaFloat>().is_err());
+    }
+
+    #[test]
+    fn integral_literals_round_trip() {
+        assert_eq!(reprint::<JavaShort>("-32768s"), "-32768s");
+        assert_eq!(reprint::<JavaByte>("127b"), "127b");
+        assert_eq!(reprint::<JavaInt>("-2147483648"), "-2147483648");
+        // `verify` adds the suffix where it prints long literals
+        assert_eq!("5l".parse::<JavaLong>().unwrap().to_string(), "5");
+        assert!("128b".parse::<JavaByte>().is_err());
+        assert!("5".parse::<JavaLong>().is_err());
+    }
+
+    #[test]
+    fn string_literals_keep_their_escapes() {
+        assert_eq!(reprint::<JavaString>("\"a b\""), "\"a b\"");
+        assert_eq!(reprint::<JavaString>("\"\\n\""), "\"\\n\"");
+        assert!("a".parse::<JavaString>().is_err());
     }
 }
@@ -0,0 +1,249 @@
+use crate::peg::{EGraph, Peg};
+use crate::primitives::{JavaDouble, JavaFloat, JavaInt, JavaLong, JavaNumber, Promoted};
+use egg::{Id, Subst};
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+
+/// Static methods, named `Class.method` as written at the call site, that
+/// neither throw nor touch the heap, so that a call is a function of its
+/// arguments. The boxing methods return equal objects for equal arguments, but
+/// not always the same object, so `==` of two calls isn't `true` (the
+/// comparison rules only fold integral operands, never references)
+const PURE_STATIC_METHODS: &[&str] = &[
+    "Math.abs", "Math.max", "Math.min", "Math.signum", "Math.sqrt", "Math.cbrt",
+    "Math.floor", "Math.ceil", "Math.rint", "Math.round", "Math.pow", "Math.exp",
+    "Math.log", "Math.log10", "Math.sin", "Math.cos", "Math.tan", "Math.hypot",
+    "Math.toRadians", "Math.toDegrees",
+    "String.valueOf",
+    "Integer.valueOf", "Long.valueOf", "Short.valueOf", "Byte.valueOf",
+    "Character.valueOf", "Boolean.valueOf", "Float.valueOf", "Double.valueOf",
+];
+
+/// The pure static methods that are only pure for primitive arguments:
+/// `String.valueOf` of an `Object` calls its `toString`, and of a `char[]` may
+/// throw a `NullPointerException`, while the `String` overloads of the boxing
+/// `valueOf`s throw a `NumberFormatException`
+const PRIMITIVE_ARGUMENT_METHODS: &[&str] = &[
+    "String.valueOf",
+    "Integer.valueOf", "Long.valueOf", "Short.valueOf", "Byte.valueOf",
+    "Character.valueOf", "Boolean.valueOf", "Float.valueOf", "Double.valueOf",
+];
+
+/// Instance methods, named `Type.method` by the receiver's type, that neither
+/// throw (given a receiver that isn't null) nor touch the heap
+const PURE_INSTANCE_METHODS: &[&str] = &[
+    "String.length", "String.isEmpty", "String.equals", "String.equalsIgnoreCase",
+    "String.hashCode", "String.toString", "String.trim",
+    "Integer.intValue", "Long.longValue", "Short.shortValue", "Byte.byteValue",
+    "Character.charValue", "Boolean.booleanValue", "Float.floatValue", "Double.doubleValue",
+];
+
+/// The methods whose calls may be treated as functions of their receiver and
+/// actuals: a built-in table of JDK methods, optionally amended by a user file
+#[derive(Clone, Debug)]
+pub struct PurityTable {
+    pure: HashSet<String>,
+    /// Where the table came from, for `ExecutionConfig::to_string`
+    pub source: String,
+}
+
+impl Default for PurityTable {
+    fn default() -> Self {
+        PurityTable::builtin()
+    }
+}
+
+impl PurityTable {
+    pub fn builtin() -> Self {
+        PurityTable {
+            pure: PURE_STATIC_METHODS.iter()
+                .chain(PURE_INSTANCE_METHODS)
+                .map(|method| method.to_string())
+                .collect(),
+            source: "built-in".to_string(),
+        }
+    }
+
+    /// The built-in table, amended by `file`. Each line of `file` is either
+    /// `pure Class.method` or `impure Class.method`; blank lines and lines
+    /// starting with `#` are ignored
+    pub fn load(file: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read purity table '{}': {}", file, e))?;
+        let mut table = PurityTable::builtin();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["pure", method] => {
+                    table.pure.insert(method.to_string());
+                }
+                ["impure", method] => {
+                    table.pure.remove(*method);
+                }
+                _ => return Err(format!(
+                    "{}:{}: expected 'pure METHOD' or 'impure METHOD', found '{}'",
+                    file, index + 1, line
+                )),
+            }
+        }
+        table.source = file.to_string();
+        Ok(table)
+    }
+
+    pub fn is_pure(&self, method: &str) -> bool {
+        self.pure.contains(method)
+    }
+}
+
+/// The name held by class `id`: a bare symbol (the method of an `invoke`), or
+/// a `(method ...)` node (the method of an `invoke-static`)
+fn method_name(egraph: &EGraph, id: Id) -> Option<String> {
+    let symbol = |id: Id| egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::Symbol(s) => Some(s.to_string()),
+        _ => None,
+    });
+    symbol(id).or_else(|| egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::MethodName(name) => symbol(*name),
+        _ => None,
+    }))
+}
+
+/// The type of the object in class `id`, when it is known: a string literal,
+/// or the result of a pure `Type.valueOf` call, which returns a `Type`
+fn receiver_type(egraph: &EGraph, id: Id) -> Option<String> {
+    if let Some(Peg::Str(_)) = egraph[id].data.constant {
+        return Some("String".to_string());
+    }
+    egraph[id].nodes.iter().find_map(|node| match node {
+        Peg::PureCall([m, _, _]) => method_name(egraph, *m)?
+            .strip_suffix(".valueOf")
+            .map(|class| class.to_string()),
+        _ => None,
+    })
+}
+
+/// The name of the method called by a `pure-call` with method `m` and receiver
+/// `r`, as it appears in a `PurityTable`. Static calls have a `unit` receiver
+pub fn call_name(egraph: &EGraph, m: Id, r: Id) -> Option<String> {
+    if egraph[r].nodes.contains(&Peg::Unit) {
+        method_name(egraph, m)
+    } else {
+        Some(format!("{}.{}", receiver_type(egraph, r)?, method_name(egraph, m)?))
+    }
+}
+
+/// Each of the actuals in class `id` is known to be of a primitive type: a
+/// number, or a boolean constant
+fn has_primitive_actuals(egraph: &EGraph, id: Id) -> bool {
+    let is_primitive = |id: &Id| {
+        let data = &egraph[*id].data;
+        data.numeric.is_some() || matches!(data.constant, Some(Peg::Bool(_)))
+    };
+    egraph[id].nodes.iter().any(|node| match node {
+        Peg::Actuals(args) => args.iter().all(is_primitive),
+        _ => false,
+    })
+}
+
+/// The static method `m` is pure according to `purity`, when called with the
+/// actuals `a`
+pub fn is_pure_static_call(purity: &Arc<PurityTable>, m: &'static str, a: &'static str)
+    -> impl Fn(&mut EGraph, Id, &Subst) -> bool
+{
+    let purity = purity.clone();
+    let m: egg::Var = m.parse().unwrap();
+    let a: egg::Var = a.parse().unwrap();
+    move |egraph, _, subst| match method_name(egraph, subst[m]) {
+        Some(name) if PRIMITIVE_ARGUMENT_METHODS.contains(&name.as_str()) => {
+            purity.is_pure(&name) && has_primitive_actuals(egraph, subst[a])
+        }
+        Some(name) => purity.is_pure(&name),
+        None => false,
+    }
+}
+
+/// The instance method `m`, called on `r`, is pure according to `purity`
+pub fn is_pure_instance_call(purity: &Arc<PurityTable>, r: &'static str, m: &'static str)
+    -> impl Fn(&mut EGraph, Id, &Subst) -> bool
+{
+    let purity = purity.clone();
+    let r: egg::Var = r.parse().unwrap();
+    let m: egg::Var = m.parse().unwrap();
+    move |egraph, _, subst| match (receiver_type(egraph, subst[r]), method_name(egraph, subst[m])) {
+        (Some(class), Some(name)) => purity.is_pure(&format!("{}.{}", class, name)),
+        _ => false,
+    }
+}
+
+/// The result of calling the JDK method `method` on constants. Only methods
+/// whose results Java specifies exactly are folded (not, say, `Math.sin`)
+pub fn fold(method: &str, receiver: Option<&Peg>, args: &[Peg]) -> Option<Peg> {
+    match (method, receiver, args) {
+        ("Math.abs", None, [a]) => Some(match a.as_number()?.unary_promotion() {
+            JavaNumber::Int(n) => Peg::Num(JavaInt::from(n.value().wrapping_abs())),
+            JavaNumber::Long(n) => Peg::Long(JavaLong::from(n.value().wrapping_abs())),
+            JavaNumber::Float(x) => Peg::Float(JavaFloat::from(x.value().abs())),
+            JavaNumber::Double(x) => Peg::Double(JavaDouble::from(x.value().abs())),
+            _ => return None,
+        }),
+        ("Math.max", None, [a, b]) => max_or_min(a, b, true),
+        ("Math.min", None, [a, b]) => max_or_min(a, b, false),
+        ("Math.sqrt", None, [a]) => {
+            Some(Peg::Double(JavaDouble::from(a.as_number()?.to_java_double().value().sqrt())))
+        }
+        ("String.length", Some(Peg::Str(s)), []) => {
+            // Escapes are kept as written, so their length isn't known
+            let contents = s.contents();
+            if contents.contains('\\') {
+                None
+            } else {
+                Some(Peg::Num(JavaInt::from(contents.encode_utf16().count() as i32)))
+            }
+        }
+        ("String.isEmpty", Some(Peg::Str(s)), []) => Some(Peg::Bool(s.contents().is_empty())),
+        ("String.equals", Some(Peg::Str(s)), [Peg::Str(t)]) => {
+            if s == t {
+                Some(Peg::Bool(true))
+            } else if s.contents().contains('\\') || t.contents().contains('\\') {
+                None
+            } else {
+                Some(Peg::Bool(false))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `Math.max` (or `Math.min`): NaN if either operand is, and `0.0` is greater
+/// than `-0.0`
+fn max_or_min(a: &Peg, b: &Peg, max: bool) -> Option<Peg> {
+    let pick = |a: f64, b: f64| {
+        if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else if a == b {
+            if a.is_sign_positive() == max { a } else { b }
+        } else if (a > b) == max {
+            a
+        } else {
+            b
+        }
+    };
+    Some(match JavaNumber::binary_promotion(a.as_number()?, b.as_number()?) {
+        Promoted::Int(a, b) => {
+            let (a, b) = (a.value(), b.value());
+            Peg::Num(JavaInt::from(if max { a.max(b) } else { a.min(b) }))
+        }
+        Promoted::Long(a, b) => {
+            let (a, b) = (a.value(), b.value());
+            Peg::Long(JavaLong::from(if max { a.max(b) } else { a.min(b) }))
+        }
+        Promoted::Float(a, b) => {
+            Peg::Float(JavaFloat::from(pick(a.value() as f64, b.value() as f64) as f32))
+        }
+        Promoted::Double(a, b) => Peg::Double(JavaDouble::from(pick(a.value(), b.value()))),
+    })
+}
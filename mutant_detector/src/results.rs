@@ -0,0 +1,340 @@
+use crate::driver::{Subject, Subjects};
+use crate::mutants_log::MutantsLog;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::str::FromStr;
+
+/// The format of the results written for each subject file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Only the `.equiv-class` file, a line of mutant ids per class
+    EquivClass,
+    /// A `.json` array holding a record per subject
+    Json,
+    /// A `.jsonl` file holding a record per subject and line
+    Jsonl,
+    /// A `.csv` file holding a row per subject
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equiv-class" => Ok(OutputFormat::EquivClass),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unknown output format '{}' (expected json, jsonl, csv or equiv-class)", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::EquivClass => "equiv-class",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ClassRecord {
+    /// The mutants of the class, in increasing order
+    pub mutants: Vec<u32>,
+    pub contains_original: bool,
+    /// The mutants' rows of the mutants log, when one was given
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub log: Vec<LogRecord>,
+}
+
+#[derive(Serialize)]
+pub struct LogRecord {
+    pub mid: u32,
+    pub operator: String,
+    pub method: String,
+    pub line: u32,
+    pub transformation: String,
+}
+
+/// What is known about a subject after its analysis. The saturation figures
+/// are those of the e-graph the subject was analyzed in, which is shared by
+/// all subjects of a file unless they are isolated
+#[derive(Serialize)]
+pub struct SubjectRecord<'a> {
+    pub source_file: &'a str,
+    pub method: &'a str,
+    pub pid: &'a str,
+    pub score: u32,
+    /// The classes of more than one program
+    pub equivalence_classes: Vec<ClassRecord>,
+    pub equivalent_to_original: &'a [u32],
+    pub duplicate_groups: &'a [Vec<u32>],
+    pub stop_reason: Option<String>,
+    pub iterations: usize,
+    pub egraph_nodes: usize,
+    /// Seconds spent saturating
+    pub saturation_time: f64,
+}
+
+impl<'a> SubjectRecord<'a> {
+    pub fn new(subject: &'a Subject, log: Option<&MutantsLog>) -> Self {
+        let result = &subject.analysis_result;
+        let equivalence_classes = result.equivalence_classes.iter()
+            .filter(|class| class.len() > 1)
+            .map(|class| {
+                let mut mutants: Vec<u32> = class.iter().copied().filter(|&mid| mid != 0).collect();
+                mutants.sort();
+                let rows = mutants.iter()
+                    .filter_map(|&mid| log?.get(mid))
+                    .map(|row| LogRecord {
+                        mid: row.mid,
+                        operator: row.operator.clone(),
+                        method: row.method.clone(),
+                        line: row.line,
+                        transformation: row.transformation.clone(),
+                    })
+                    .collect();
+                ClassRecord { mutants, contains_original: class.contains(&0), log: rows }
+            })
+            .collect();
+        SubjectRecord {
+            source_file: &subject.source_file,
+            method: &subject.method,
+            pid: &subject.pid,
+            score: result.score,
+            equivalence_classes,
+            equivalent_to_original: &result.equivalent_to_original,
+            duplicate_groups: &result.duplicate_groups,
+            stop_reason: result.stop_reason.as_ref().map(|reason| format!("{:?}", reason)),
+            iterations: result.iterations,
+            egraph_nodes: result.egraph_nodes,
+            saturation_time: result.saturation_time,
+        }
+    }
+}
+
+/// Write a record per subject of `subjects` to `file`, in `format`. Fails
+/// for `OutputFormat::EquivClass`, which has no records
+pub fn write_records(
+    subjects: &Subjects,
+    format: OutputFormat,
+    log: Option<&MutantsLog>,
+    file: &str,
+) -> Result<(), Error> {
+    if format == OutputFormat::EquivClass {
+        return Err(Error::new(ErrorKind::InvalidInput, "equiv-class files aren't written as records"));
+    }
+    let records: Vec<SubjectRecord> = subjects.subjects.iter()
+        .map(|subject| SubjectRecord::new(subject, log))
+        .collect();
+    let mut file = File::create(file)?;
+    write_records_to(&mut file, &records, format)
+}
+
+fn write_records_to(out: &mut impl Write, records: &[SubjectRecord], format: OutputFormat) -> Result<(), Error> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)
+        }
+        OutputFormat::Jsonl => records.iter().try_for_each(|record| {
+            serde_json::to_writer(&mut *out, record)?;
+            writeln!(out)
+        }),
+        OutputFormat::Csv => {
+            writeln!(out, "source_file,method,pid,score,equivalence_classes,equivalent_to_original,duplicate_groups,stop_reason,iterations,egraph_nodes,saturation_time,mutants_log")?;
+            records.iter().try_for_each(|record| writeln!(out, "{}", csv_row(record)))
+        }
+        OutputFormat::EquivClass => Err(Error::new(ErrorKind::InvalidInput, "equiv-class files aren't written as records")),
+    }
+}
+
+/// A CSV row for `record`. Classes and duplicate groups are separated by `;`,
+/// and their mutants by spaces, with `0` standing for the original. Mutants
+/// log rows are separated by `;` too, as `id:OPERATOR:line:transformation`
+fn csv_row(record: &SubjectRecord) -> String {
+    let mids = |mids: &[u32]| mids.iter().map(|mid| mid.to_string()).collect::<Vec<_>>().join(" ");
+    let classes = record.equivalence_classes.iter()
+        .map(|class| {
+            let original = if class.contains_original { Some(0) } else { None };
+            original.into_iter()
+                .chain(class.mutants.iter().copied())
+                .map(|mid| mid.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+    let log = record.equivalence_classes.iter()
+        .flat_map(|class| &class.log)
+        .map(|row| format!("{}:{}:{}:{}", row.mid, row.operator, row.line, row.transformation))
+        .collect::<Vec<_>>()
+        .join(";");
+    [
+        csv_field(record.source_file),
+        csv_field(record.method),
+        csv_field(record.pid),
+        record.score.to_string(),
+        csv_field(&classes),
+        mids(record.equivalent_to_original),
+        record.duplicate_groups.iter().map(|group| mids(group)).collect::<Vec<_>>().join(";"),
+        csv_field(record.stop_reason.as_deref().unwrap_or("")),
+        record.iterations.to_string(),
+        record.egraph_nodes.to_string(),
+        record.saturation_time.to_string(),
+        csv_field(&log),
+    ].join(",")
+}
+
+/// `field`, quoted if it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write the `.equiv-class` file for `subjects`: for each subject, a line per
+/// class of sorted mutant ids (`0` being the original, which is left out when
+/// it's alone), with a blank line between subjects
+pub fn write_subjects_to_single_file(subjects: &Subjects, file: &str) -> Result<(), Error> {
+    let mut file = File::create(file)?;
+    let mut equiv_file_contents = vec![];
+    for subject in &subjects.subjects {
+        equiv_file_contents.push(get_equiv_file_contents_for_subject(subject));
+    }
+
+    let file_contents = equiv_file_contents.join("\n");
+    file.write_all(file_contents.as_bytes())
+}
+
+fn get_equiv_file_contents_for_subject(subject: &Subject) -> String {
+    let ar = &subject.analysis_result;
+    let mut equiv_classes_as_strings = vec![];
+    for equiv_class in &ar.equivalence_classes {
+        if equiv_class.len() == 1 && equiv_class.iter().next().unwrap() == &0 {
+            continue;
+        }
+        let equiv_class_as_string: String = itertools::sorted(equiv_class)
+            .iter()
+            .map(|id| (**id).to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        equiv_classes_as_strings.push(equiv_class_as_string);
+    }
+    format!("{}\n", equiv_classes_as_strings.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::AnalysisResult;
+    use crate::mutants_log::MutantsLogRow;
+
+    fn subject() -> Subject {
+        Subject {
+            source_file: "src/Foo.java".to_string(),
+            method: "Foo@max(int,int)".to_string(),
+            pid: "12".to_string(),
+            mutants: vec![],
+            analysis_result: AnalysisResult {
+                score: 3,
+                equivalence_classes: vec![
+                    [0, 2].iter().copied().collect(),
+                    [3, 4, 5].iter().copied().collect(),
+                    [1].iter().copied().collect(),
+                ],
+                equivalent_to_original: vec![2],
+                duplicate_groups: vec![vec![3, 4, 5]],
+                iterations: 4,
+                egraph_nodes: 20,
+                saturation_time: 0.5,
+                ..AnalysisResult::default()
+            },
+        }
+    }
+
+    fn log() -> MutantsLog {
+        let row = MutantsLogRow {
+            mid: 2,
+            operator: "ROR".to_string(),
+            original: ">".to_string(),
+            replacement: ">=".to_string(),
+            method: "Foo@max(int,int)".to_string(),
+            line: 7,
+            transformation: "a > b |==> a >= b".to_string(),
+        };
+        MutantsLog { file: "mutants.log".to_string(), rows: [(2, row)].iter().cloned().collect() }
+    }
+
+    fn written(format: OutputFormat) -> String {
+        let (subject, log) = (subject(), log());
+        let records = vec![SubjectRecord::new(&subject, Some(&log))];
+        let mut out = vec![];
+        write_records_to(&mut out, &records, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("Foo@max(int)"), "Foo@max(int)");
+        assert_eq!(csv_field("Foo@max(int,int)"), "\"Foo@max(int,int)\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let csv = written(OutputFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            "src/Foo.java,\"Foo@max(int,int)\",12,3,0 2;3 4 5,2,3 4 5,,4,20,0.5,2:ROR:7:a > b |==> a >= b"
+        );
+        assert_eq!(lines[0].split(',').count(), 12);
+    }
+
+    #[test]
+    fn json_records_hold_every_field() {
+        let json: serde_json::Value = serde_json::from_str(&written(OutputFormat::Json)).unwrap();
+        let record = &json[0];
+        assert_eq!(record["method"], "Foo@max(int,int)");
+        assert_eq!(record["score"], 3);
+        assert_eq!(record["equivalent_to_original"], serde_json::json!([2]));
+        assert_eq!(record["duplicate_groups"], serde_json::json!([[3, 4, 5]]));
+        assert_eq!(record["equivalence_classes"][0]["contains_original"], true);
+        assert_eq!(record["equivalence_classes"][0]["log"][0]["operator"], "ROR");
+        // Classes without log rows leave the log out
+        assert!(record["equivalence_classes"][1].get("log").is_none());
+        assert_eq!(record["stop_reason"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn jsonl_writes_a_record_per_line() {
+        let jsonl = written(OutputFormat::Jsonl);
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record, serde_json::from_str::<serde_json::Value>(&written(OutputFormat::Json)).unwrap()[0]);
+    }
+
+    #[test]
+    fn equiv_class_files_have_no_records() {
+        let file = std::env::temp_dir().join("results_tests.equiv-class");
+        let _ = std::fs::remove_file(&file);
+        let subjects = Subjects::default();
+        let error = write_records(&subjects, OutputFormat::EquivClass, None, file.to_str().unwrap()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(!file.exists());
+    }
+}
@@ -1,51 +1,510 @@
 use crate::peg::*;
-use egg::{rewrite as rw, Rewrite};
+use crate::execution_config::ExecutionConfig;
+use crate::primitives::NumericType;
+use crate::purity::{is_pure_instance_call, is_pure_static_call, PurityTable};
+use crate::rule_file::RuleSpec;
+use egg::{rewrite as rw, Id, Rewrite, Subst};
+use std::sync::Arc;
 
 pub type RewriteSystem = [Rewrite<Peg, PegAnalysis>];
 
-#[allow(unused_parens)]
+pub type RuleCondition = Box<dyn Fn(&mut EGraph, Id, &Subst) -> bool + Send + Sync>;
+
+/// Whether a rule preserves Java semantics for all `int`, `long`, `float`,
+/// `double`, `boolean` and `String` operands. Unsound rules may still be useful
+/// heuristically, but can equate mutants that aren't equivalent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Soundness {
+    Sound,
+    Unsound,
+}
+
+impl ToString for Soundness {
+    fn to_string(&self) -> String {
+        match self {
+            Soundness::Sound => "sound".to_string(),
+            Soundness::Unsound => "unsound".to_string(),
+        }
+    }
+}
+
+/// A rewrite together with the patterns and condition it was built from, so
+/// that it can be checked by `verify::verify_rules`
+pub struct Rule {
+    pub rewrite: Rewrite<Peg, PegAnalysis>,
+    pub lhs: String,
+    pub rhs: String,
+    pub condition: Option<RuleCondition>,
+    pub soundness: Soundness,
+}
+
+impl Rule {
+    pub fn name(&self) -> &str {
+        self.rewrite.name()
+    }
+}
+
+macro_rules! rule {
+    ($name:expr, $soundness:ident; $lhs:tt => $rhs:tt) => {
+        Rule {
+            rewrite: rw!($name; $lhs => $rhs),
+            lhs: $lhs.to_string(),
+            rhs: $rhs.to_string(),
+            condition: None,
+            soundness: Soundness::$soundness,
+        }
+    };
+    ($name:expr, $soundness:ident; $lhs:tt => $rhs:tt if $($cond:expr),+) => {
+        Rule {
+            rewrite: rw!($name; $lhs => $rhs if all_of(vec![$(Box::new($cond) as RuleCondition),+])),
+            lhs: $lhs.to_string(),
+            rhs: $rhs.to_string(),
+            condition: Some(Box::new(all_of(vec![$(Box::new($cond) as RuleCondition),+]))),
+            soundness: Soundness::$soundness,
+        }
+    };
+}
+
+/// The groups the built-in rules are organized in, which can be enabled and
+/// disabled separately
+pub const RULE_GROUPS: &[&str] = &[
+    "arithmetic", "division", "remainder", "ordering", "comparison", "boolean",
+    "bitwise", "shift", "phi", "heap", "loop", "call",
+];
+
+/// Check that each of `groups` is one of `RULE_GROUPS`
+pub fn check_rule_groups(groups: &[String]) -> Result<(), String> {
+    match groups.iter().find(|group| !RULE_GROUPS.contains(&group.as_str())) {
+        Some(group) => Err(format!("Unknown rule group '{}' (expected one of {})", group, RULE_GROUPS.join(", "))),
+        None => Ok(()),
+    }
+}
+
+/// A condition that holds when each of `conditions` does
+pub(crate) fn all_of(conditions: Vec<RuleCondition>) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    move |egraph, id, subst| conditions.iter().all(|condition| condition(egraph, id, subst))
+}
+
 pub fn rw_rules() -> Box<RewriteSystem> {
-    let rules = [
-        // Arithmetic
-        rw!("commute-add";   "(+ ?a ?b)"         => "(+ ?b ?a)"),
-        rw!("commute-mul";   "(* ?a ?b)"         => "(* ?b ?a)"),
-        rw!("associate-add"; "(+ ?a (+ ?b ?c))"  => "(+ (+ ?a ?b) ?c)"),
-        rw!("associate-mul"; "(* ?a (* ?b ?c))"  => "(* (* ?a ?b) ?c)"),
-        rw!("add-ident";     "(+ ?a 0)"          => "?a" if is_not_const("?a")),
-        rw!("mul-bot";       "(* ?a 0)"          => "0" if is_not_const("?a")),
-        rw!("mul-bot-long";  "(* ?a 0l)"         => "0l" if is_not_const("?a")),
-        rw!("mul-ident";     "(* ?a 1)"          => "?a" if is_not_const("?a")),
-        rw!("neg-zero";      "(--- 0)"           => "0"),
-        rw!("add-inv";       "(+ ?a (--- ?a))"   => "0" if is_not_const("?a")),
-        rw!("sub-to-add";    "(- ?a ?b)"         => "(+ ?a (--- ?b))"),
-        //Division
-        rw!("commute-div"; "(/ ?a ?b)" => "(/ ?a ?b)"),
-        rw!("div-ident"; "(/ ?a 1)" => "?a"),
-        rw!("div-by-self"; "(/ ?a ?a)" => "1"),
-        rw!("div-zero"; "(/ ?a 0)" => "error"),
-        rw!("mul-div-cancel"; "(* ?a (/ 1 ?a))" => "1"),
-        rw!("associate-div"; "(/ (/ ?a ?b) ?c)" => "(/ ?a (* ?b ?c))"), 
-        
-        //Remainder
-        rw!("rem-zero-divisor"; "(% ?a 1)" => "0"),
-        rw!("rem-same-num"; "(% ?a ?a)" => "0"),
-        rw!("rem-zero-numerator"; "(% 0 ?a)" => "0" if is_not_const("?a")),
-        rw!("rem-additive"; "(% (+ ?a ?n) ?n)" => "(% ?a ?n)"),
-        rw!("rem-subtractive"; "(% (- ?a ?n) ?n)" => "(% ?a ?n)"),
-        rw!("rem-multiplicative"; "(% (* ?k ?a) ?a)" => "0"),
-        rw!("rem-distrib-add"; "(% (+ ?a ?b) ?n)" => "(% (+ (% ?a ?n) (% ?b ?n)) ?n)"),
-        rw!("rem-distrib-sub"; "(% (- ?a ?b) ?n)" => "(% (+ (- (% ?a ?n) (% ?b ?n)) ?n) ?n)"),
-        rw!("rem-negation"; "(% (--- ?a) ?n)" => "(% (+ ?n (--- (% ?a ?n))) ?n)"),
-
-
-        // Ordering
-        rw!("lt-comp";      "(< ?a ?b)"     => "(! (>= ?a ?b))"),
-        rw!("gt-comp";      "(> ?a ?b)"     => "(! (<= ?a ?b))"),
-        rw!("lte-comp";     "(<= ?a ?b)"    => "(! (> ?a ?b))"),
-        rw!("gte-comp";     "(>= ?a ?b)"    => "(! (< ?a ?b))"),
-        rw!("gte-split";    "(>= ?a ?b)"    => "(|| (> ?a ?b) (== ?a ?b))"),
-        rw!("lte-split";    "(<= ?a ?b)"    => "(|| (< ?a ?b) (== ?a ?b))"),
-        
-    ];
-    Box::new(rules)
+    rules().into_iter().map(|rule| rule.rewrite).collect()
+}
+
+/// The rules to saturate with under `config`
+pub fn rw_rules_for(config: &ExecutionConfig) -> Box<RewriteSystem> {
+    rules_for(config).into_iter().map(|rule| rule.rewrite).collect()
+}
+
+/// The built-in rules of `config`'s active groups (unless `config` replaces
+/// them) along with the rules of `config`'s rule file, which take the place of
+/// built-in rules of the same name
+pub fn rules_for(config: &ExecutionConfig) -> Vec<Rule> {
+    let user_rules: Vec<Rule> = config.user_rules.iter().map(RuleSpec::to_rule).collect();
+    let mut rules: Vec<Rule> = if config.replace_builtin_rules {
+        vec![]
+    } else {
+        config.rule_groups.iter().flat_map(|group| group_rules(group, &config.purity)).collect()
+    };
+    rules.retain(|rule| !user_rules.iter().any(|user_rule| user_rule.name() == rule.name()));
+    rules.extend(user_rules);
+    rules
+        .into_iter()
+        .filter(|rule| !config.exclude_unsound_rules || rule.soundness == Soundness::Sound)
+        .collect()
+}
+
+// Rules are tagged `Unsound` when `verify-rules` finds a counterexample. Besides
+// division and remainder by zero and overflow, an `int` literal on the right
+// hand side is unsound when the left hand side may be a `long` (as in `mul-bot`).
+// Where a rule only holds for some operands, the range and type analyses in
+// `PegAnalysisData` guard it (`is_nonzero`, `fits_without_overflow`, ...).
+// Rules that fail for `float` and `double`, because of rounding, NaN or -0.0,
+// only apply to operands known to be integral (`is_integral`)
+pub fn rules() -> Vec<Rule> {
+    rules_with_purity(&Arc::new(PurityTable::builtin()))
+}
+
+/// The rules, with calls to the methods `purity` lists treated as functions
+pub fn rules_with_purity(purity: &Arc<PurityTable>) -> Vec<Rule> {
+    RULE_GROUPS.iter().flat_map(|group| group_rules(group, purity)).collect()
+}
+
+/// The built-in rules in `group`, one of `RULE_GROUPS`
+#[allow(unused_parens)]
+pub fn group_rules(group: &str, purity: &Arc<PurityTable>) -> Vec<Rule> {
+    match group {
+        "arithmetic" => vec![
+            // `+` also concatenates strings, which doesn't commute
+            rule!("commute-add", Sound;   "(+ ?a ?b)"        => "(+ ?b ?a)" if is_numeric("?a"), is_numeric("?b")),
+            rule!("commute-mul", Sound;   "(* ?a ?b)"        => "(* ?b ?a)"),
+            rule!("associate-add", Sound; "(+ ?a (+ ?b ?c))" => "(+ (+ ?a ?b) ?c)"
+                  if is_integral("?a"), is_integral("?b"), is_integral("?c")),
+            rule!("associate-mul", Sound; "(* ?a (* ?b ?c))" => "(* (* ?a ?b) ?c)"
+                  if is_integral("?a"), is_integral("?b"), is_integral("?c")),
+            rule!("add-ident", Sound;     "(+ ?a 0)"         => "?a" if is_not_const("?a"), is_integral("?a")),
+            rule!("mul-bot", Unsound;     "(* ?a 0)"         => "0" if is_not_const("?a")),
+            rule!("mul-bot-long", Sound;  "(* ?a 0l)"        => "0l" if is_not_const("?a"), is_integral("?a")),
+            rule!("mul-ident", Sound;     "(* ?a 1)"         => "?a" if is_not_const("?a")),
+            rule!("neg-zero", Sound;      "(--- 0)"          => "0"),
+            rule!("add-inv", Unsound;     "(+ ?a (--- ?a))"  => "0" if is_not_const("?a")),
+            rule!("sub-to-add", Sound;    "(- ?a ?b)"        => "(+ ?a (--- ?b))"),
+        ],
+        "division" => vec![
+            rule!("commute-div", Sound; "(/ ?a ?b)" => "(/ ?a ?b)"),
+            rule!("div-ident", Sound; "(/ ?a 1)" => "?a"),
+            rule!("div-by-self", Sound; "(/ ?a ?a)" => "1" if is_int("?a"), is_nonzero("?a")),
+            rule!("div-by-self-long", Sound; "(/ ?a ?a)" => "1l" if is_long("?a"), is_nonzero("?a")),
+            rule!("div-zero", Sound; "(/ ?a 0)" => "error" if is_integral("?a")),
+            rule!("mul-div-cancel", Unsound; "(* ?a (/ 1 ?a))" => "1"),
+            rule!("associate-div", Sound; "(/ (/ ?a ?b) ?c)" => "(/ ?a (* ?b ?c))"
+                  if fits_without_overflow("/", "?a", "?b"),
+                  fits_without_overflow("*", "?b", "?c")),
+        ],
+        "remainder" => vec![
+            rule!("rem-zero-divisor", Sound; "(% ?a 1)" => "0" if is_int("?a")),
+            rule!("rem-zero-divisor-long", Sound; "(% ?a 1)" => "0l" if is_long("?a")),
+            rule!("rem-same-num", Sound; "(% ?a ?a)" => "0" if is_int("?a"), is_nonzero("?a")),
+            rule!("rem-same-num-long", Sound; "(% ?a ?a)" => "0l" if is_long("?a"), is_nonzero("?a")),
+            rule!("rem-zero-numerator", Sound; "(% 0 ?a)" => "0" if is_int("?a"), is_nonzero("?a")),
+            rule!("rem-additive", Sound; "(% (+ ?a ?n) ?n)" => "(% ?a ?n)"
+                  if is_nonneg("?a"), is_nonneg("?n"), fits_without_overflow("+", "?a", "?n")),
+            rule!("rem-subtractive", Unsound; "(% (- ?a ?n) ?n)" => "(% ?a ?n)"),
+            rule!("rem-multiplicative", Sound; "(% (* ?k ?a) ?a)" => "0"
+                  if is_int("?k"), is_int("?a"), is_nonzero("?a"), fits_without_overflow("*", "?k", "?a")),
+            rule!("rem-distrib-add", Unsound; "(% (+ ?a ?b) ?n)" => "(% (+ (% ?a ?n) (% ?b ?n)) ?n)"),
+            rule!("rem-distrib-sub", Unsound; "(% (- ?a ?b) ?n)" => "(% (+ (- (% ?a ?n) (% ?b ?n)) ?n) ?n)"),
+            rule!("rem-negation", Unsound; "(% (--- ?a) ?n)" => "(% (+ ?n (--- (% ?a ?n))) ?n)"),
+        ],
+        "ordering" => vec![
+            // Every comparison with NaN is false, so negating one only flips
+            // the comparison for integral operands
+            rule!("lt-comp", Sound;   "(< ?a ?b)"  => "(! (>= ?a ?b))" if is_integral("?a"), is_integral("?b")),
+            rule!("gt-comp", Sound;   "(> ?a ?b)"  => "(! (<= ?a ?b))" if is_integral("?a"), is_integral("?b")),
+            rule!("lte-comp", Sound;  "(<= ?a ?b)" => "(! (> ?a ?b))" if is_integral("?a"), is_integral("?b")),
+            rule!("gte-comp", Sound;  "(>= ?a ?b)" => "(! (< ?a ?b))" if is_integral("?a"), is_integral("?b")),
+            rule!("gte-split", Sound; "(>= ?a ?b)" => "(|| (> ?a ?b) (== ?a ?b))"),
+            rule!("lte-split", Sound; "(<= ?a ?b)" => "(|| (< ?a ?b) (== ?a ?b))"),
+            rule!("not-lt", Sound;    "(! (< ?a ?b))"  => "(>= ?a ?b)" if is_integral("?a"), is_integral("?b")),
+            rule!("not-gt", Sound;    "(! (> ?a ?b))"  => "(<= ?a ?b)" if is_integral("?a"), is_integral("?b")),
+            rule!("not-lte", Sound;   "(! (<= ?a ?b))" => "(> ?a ?b)" if is_integral("?a"), is_integral("?b")),
+            rule!("not-gte", Sound;   "(! (>= ?a ?b))" => "(< ?a ?b)" if is_integral("?a"), is_integral("?b")),
+        ],
+        "comparison" => vec![
+            // Comparison. Swapping the operands of a comparison changes which one
+            // is evaluated first, which only matters when both throw, and `error`
+            // doesn't tell exceptions apart. NaN isn't equal to itself, so a value
+            // compared with itself only folds when it is integral
+            rule!("flip-lt", Sound;       "(< ?a ?b)"       => "(> ?b ?a)"),
+            rule!("flip-gt", Sound;       "(> ?a ?b)"       => "(< ?b ?a)"),
+            rule!("flip-lte", Sound;      "(<= ?a ?b)"      => "(>= ?b ?a)"),
+            rule!("flip-gte", Sound;      "(>= ?a ?b)"      => "(<= ?b ?a)"),
+            rule!("commute-eq", Sound;    "(== ?a ?b)"      => "(== ?b ?a)"),
+            rule!("commute-neq", Sound;   "(!= ?a ?b)"      => "(!= ?b ?a)"),
+            rule!("not-eq", Sound;        "(! (== ?a ?b))"  => "(!= ?a ?b)"),
+            rule!("not-neq", Sound;       "(! (!= ?a ?b))"  => "(== ?a ?b)"),
+            rule!("neq-to-not-eq", Sound; "(!= ?a ?b)"      => "(! (== ?a ?b))"),
+            rule!("eq-self", Sound;       "(== ?a ?a)"      => "true" if is_pure("?a"), is_integral("?a")),
+            rule!("neq-self", Sound;      "(!= ?a ?a)"      => "false" if is_pure("?a"), is_integral("?a")),
+            rule!("lt-self", Sound;       "(< ?a ?a)"       => "false" if is_pure("?a")),
+            rule!("gt-self", Sound;       "(> ?a ?a)"       => "false" if is_pure("?a")),
+            rule!("lte-self", Sound;      "(<= ?a ?a)"      => "true" if is_pure("?a"), is_integral("?a")),
+            rule!("gte-self", Sound;      "(>= ?a ?a)"      => "true" if is_pure("?a"), is_integral("?a")),
+        ],
+        "boolean" => vec![
+            // Boolean. `&&` and `||` only evaluate their right operand when the
+            // left one doesn't decide the result, so rules that reorder operands,
+            // or drop one that would have been evaluated, need it to be pure
+            rule!("not-not", Sound;             "(! (! ?a))"            => "?a"),
+            rule!("de-morgan-land", Sound;      "(! (&& ?a ?b))"        => "(|| (! ?a) (! ?b))"),
+            rule!("de-morgan-lor", Sound;       "(! (|| ?a ?b))"        => "(&& (! ?a) (! ?b))"),
+            rule!("de-morgan-land-rev", Sound;  "(|| (! ?a) (! ?b))"    => "(! (&& ?a ?b))"),
+            rule!("de-morgan-lor-rev", Sound;   "(&& (! ?a) (! ?b))"    => "(! (|| ?a ?b))"),
+            rule!("associate-land", Sound;      "(&& ?a (&& ?b ?c))"    => "(&& (&& ?a ?b) ?c)"),
+            rule!("associate-lor", Sound;       "(|| ?a (|| ?b ?c))"    => "(|| (|| ?a ?b) ?c)"),
+            rule!("commute-land", Sound;        "(&& ?a ?b)"            => "(&& ?b ?a)" if is_pure("?a"), is_pure("?b")),
+            rule!("commute-lor", Sound;         "(|| ?a ?b)"            => "(|| ?b ?a)" if is_pure("?a"), is_pure("?b")),
+            rule!("land-idem", Sound;           "(&& ?a ?a)"            => "?a"),
+            rule!("lor-idem", Sound;            "(|| ?a ?a)"            => "?a"),
+            rule!("land-absorb", Sound;         "(&& ?a (|| ?a ?b))"    => "?a"),
+            rule!("lor-absorb", Sound;          "(|| ?a (&& ?a ?b))"    => "?a"),
+            rule!("land-true-left", Sound;      "(&& true ?a)"          => "?a"),
+            rule!("land-true-right", Sound;     "(&& ?a true)"          => "?a"),
+            rule!("land-false-left", Sound;     "(&& false ?a)"         => "false"),
+            rule!("land-false-right", Sound;    "(&& ?a false)"         => "false" if is_pure("?a")),
+            rule!("lor-false-left", Sound;      "(|| false ?a)"         => "?a"),
+            rule!("lor-false-right", Sound;     "(|| ?a false)"         => "?a"),
+            rule!("lor-true-left", Sound;       "(|| true ?a)"          => "true"),
+            rule!("lor-true-right", Sound;      "(|| ?a true)"          => "true" if is_pure("?a")),
+            rule!("land-contradiction", Sound;  "(&& ?a (! ?a))"        => "false" if is_pure("?a")),
+            rule!("lor-excluded-middle", Sound; "(|| ?a (! ?a))"        => "true" if is_pure("?a")),
+        ],
+        "bitwise" => vec![
+            // Bitwise. Bitwise operators commute with sign extension, so these
+            // hold for mixed int and long operands too, except where an int
+            // literal replaces what may be a long
+            rule!("commute-and", Sound;        "(& ?a ?b)"             => "(& ?b ?a)"),
+            rule!("commute-or", Sound;         "(| ?a ?b)"             => "(| ?b ?a)"),
+            rule!("commute-xor", Sound;        "(^ ?a ?b)"             => "(^ ?b ?a)"),
+            rule!("associate-and", Sound;      "(& ?a (& ?b ?c))"      => "(& (& ?a ?b) ?c)"),
+            rule!("associate-or", Sound;       "(| ?a (| ?b ?c))"      => "(| (| ?a ?b) ?c)"),
+            rule!("associate-xor", Sound;      "(^ ?a (^ ?b ?c))"      => "(^ (^ ?a ?b) ?c)"),
+            rule!("and-zero", Unsound;         "(& ?a 0)"              => "0" if is_not_const("?a")),
+            rule!("and-zero-int", Sound;       "(& ?a 0)"              => "0" if is_int("?a")),
+            rule!("and-zero-long", Sound;      "(& ?a 0l)"             => "0l" if is_not_const("?a")),
+            rule!("and-ones", Sound;           "(& ?a -1)"             => "?a"),
+            rule!("and-self", Sound;           "(& ?a ?a)"             => "?a"),
+            rule!("or-zero", Sound;            "(| ?a 0)"              => "?a"),
+            rule!("or-ones", Unsound;          "(| ?a -1)"             => "-1" if is_not_const("?a")),
+            rule!("or-ones-int", Sound;        "(| ?a -1)"             => "-1" if is_int("?a")),
+            rule!("or-ones-long", Sound;       "(| ?a -1l)"            => "-1l" if is_not_const("?a")),
+            rule!("or-self", Sound;            "(| ?a ?a)"             => "?a"),
+            rule!("xor-zero", Sound;           "(^ ?a 0)"              => "?a"),
+            rule!("xor-ones", Sound;           "(^ ?a -1)"             => "(~ ?a)"),
+            rule!("xor-self", Unsound;         "(^ ?a ?a)"             => "0" if is_not_const("?a")),
+            rule!("xor-self-int", Sound;       "(^ ?a ?a)"             => "0" if is_int("?a")),
+            rule!("xor-self-long", Sound;      "(^ ?a ?a)"             => "0l" if is_long("?a")),
+            rule!("bin-neg-involution", Sound; "(~ (~ ?a))"            => "?a"),
+            rule!("bin-neg-to-sub", Sound;     "(~ ?a)"                => "(- (--- ?a) 1)"),
+            rule!("de-morgan-and", Sound;      "(~ (& ?a ?b))"         => "(| (~ ?a) (~ ?b))"),
+            rule!("de-morgan-or", Sound;       "(~ (| ?a ?b))"         => "(& (~ ?a) (~ ?b))"),
+            rule!("de-morgan-and-rev", Sound;  "(| (~ ?a) (~ ?b))"     => "(~ (& ?a ?b))"),
+            rule!("de-morgan-or-rev", Sound;   "(& (~ ?a) (~ ?b))"     => "(~ (| ?a ?b))"),
+            rule!("or-disjoint-to-add", Sound; "(| ?a ?b)"             => "(+ ?a ?b)"
+                  if are_bit_disjoint_constants("?a", "?b")),
+        ],
+        "shift" => vec![
+            rule!("urshift-nonneg", Sound;  "(>>> ?a ?b)"          => "(>> ?a ?b)" if is_nonneg("?a")),
+            rule!("srshift-nonneg", Sound;  "(>> ?a ?b)"           => "(>>> ?a ?b)" if is_nonneg("?a")),
+            rule!("lshift-zero", Sound;     "(<< ?a 0)"            => "?a"),
+            rule!("srshift-zero", Sound;    "(>> ?a 0)"            => "?a"),
+            rule!("urshift-zero", Sound;    "(>>> ?a 0)"           => "?a"),
+            rule!("lshift-compose", Sound;  "(<< (<< ?a ?b) ?c)"   => "(<< ?a (+ ?b ?c))" if shifts_compose("?b", "?c")),
+            rule!("srshift-compose", Sound; "(>> (>> ?a ?b) ?c)"   => "(>> ?a (+ ?b ?c))" if shifts_compose("?b", "?c")),
+            rule!("urshift-compose", Sound; "(>>> (>>> ?a ?b) ?c)" => "(>>> ?a (+ ?b ?c))" if shifts_compose("?b", "?c")),
+        ],
+        "phi" => vec![
+            // Phi. Dropping the guard is only sound when evaluating it can't throw
+            rule!("phi-true", Sound;          "(phi true ?a ?b)"              => "?a"),
+            rule!("phi-false", Sound;         "(phi false ?a ?b)"             => "?b"),
+            rule!("phi-same", Sound;          "(phi ?c ?a ?a)"                => "?a" if is_pure("?c")),
+            rule!("phi-not", Sound;           "(phi (! ?c) ?a ?b)"            => "(phi ?c ?b ?a)"),
+            rule!("phi-nested-then", Sound;   "(phi ?c (phi ?c ?a ?b) ?d)"    => "(phi ?c ?a ?d)"),
+            rule!("phi-nested-else", Sound;   "(phi ?c ?a (phi ?c ?b ?d))"    => "(phi ?c ?a ?d)"),
+            rule!("phi-true-false", Sound;    "(phi ?c true false)"           => "?c"),
+            rule!("phi-false-true", Sound;    "(phi ?c false true)"           => "(! ?c)"),
+            rule!("phi-true-to-or", Sound;    "(phi ?c true ?b)"              => "(|| ?c ?b)"),
+            rule!("phi-false-to-and", Sound;  "(phi ?c ?a false)"             => "(&& ?c ?a)"),
+
+            // Phi distribution. Operators applied to both branches are factored
+            // out of the phi, which is enough for a mutated branch to meet the
+            // same operator applied to the whole conditional, without growing
+            // the e-graph
+            rule!("phi-factor-neg", Sound;     "(phi ?c (--- ?a) (--- ?b))"   => "(--- (phi ?c ?a ?b))"),
+            rule!("phi-factor-not", Sound;     "(phi ?c (! ?a) (! ?b))"       => "(! (phi ?c ?a ?b))"),
+            rule!("phi-factor-bin-neg", Sound; "(phi ?c (~ ?a) (~ ?b))"       => "(~ (phi ?c ?a ?b))"),
+            rule!("phi-factor-add-l", Sound;   "(phi ?c (+ ?d ?a) (+ ?d ?b))" => "(+ ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-add-r", Sound;   "(phi ?c (+ ?a ?d) (+ ?b ?d))" => "(+ (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-sub-l", Sound;   "(phi ?c (- ?d ?a) (- ?d ?b))" => "(- ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-sub-r", Sound;   "(phi ?c (- ?a ?d) (- ?b ?d))" => "(- (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-mul-l", Sound;   "(phi ?c (* ?d ?a) (* ?d ?b))" => "(* ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-mul-r", Sound;   "(phi ?c (* ?a ?d) (* ?b ?d))" => "(* (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-div-l", Sound;   "(phi ?c (/ ?d ?a) (/ ?d ?b))" => "(/ ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-div-r", Sound;   "(phi ?c (/ ?a ?d) (/ ?b ?d))" => "(/ (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-rem-l", Sound;   "(phi ?c (% ?d ?a) (% ?d ?b))" => "(% ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-rem-r", Sound;   "(phi ?c (% ?a ?d) (% ?b ?d))" => "(% (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-eq-l", Sound;    "(phi ?c (== ?d ?a) (== ?d ?b))" => "(== ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-eq-r", Sound;    "(phi ?c (== ?a ?d) (== ?b ?d))" => "(== (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-neq-l", Sound;   "(phi ?c (!= ?d ?a) (!= ?d ?b))" => "(!= ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-neq-r", Sound;   "(phi ?c (!= ?a ?d) (!= ?b ?d))" => "(!= (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-lt-l", Sound;    "(phi ?c (< ?d ?a) (< ?d ?b))" => "(< ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-lt-r", Sound;    "(phi ?c (< ?a ?d) (< ?b ?d))" => "(< (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-lte-l", Sound;   "(phi ?c (<= ?d ?a) (<= ?d ?b))" => "(<= ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-lte-r", Sound;   "(phi ?c (<= ?a ?d) (<= ?b ?d))" => "(<= (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-and-l", Sound;   "(phi ?c (& ?d ?a) (& ?d ?b))" => "(& ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-and-r", Sound;   "(phi ?c (& ?a ?d) (& ?b ?d))" => "(& (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-or-l", Sound;    "(phi ?c (| ?d ?a) (| ?d ?b))" => "(| ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-or-r", Sound;    "(phi ?c (| ?a ?d) (| ?b ?d))" => "(| (phi ?c ?a ?b) ?d)"),
+            rule!("phi-factor-xor-l", Sound;   "(phi ?c (^ ?d ?a) (^ ?d ?b))" => "(^ ?d (phi ?c ?a ?b))"),
+            rule!("phi-factor-xor-r", Sound;   "(phi ?c (^ ?a ?d) (^ ?b ?d))" => "(^ (phi ?c ?a ?b) ?d)"),
+        ],
+        "heap" => vec![
+            // Heap. Return nodes need no rules of their own: once these rules
+            // bring two heaps into the same class, congruence equates return
+            // nodes with equal values over them
+            rule!("rd-over-wr-same", Sound;      "(rd ?p (wr ?p ?v ?h))"          => "?v"),
+            rule!("rd-over-wr-distinct", Sound;  "(rd ?p (wr ?q ?v ?h))"          => "(rd ?p ?h)"
+                  if are_distinct_paths("?p", "?q")),
+            rule!("wr-over-wr-same", Sound;      "(wr ?p ?v (wr ?p ?u ?h))"       => "(wr ?p ?v ?h)"),
+            rule!("wr-over-wr-distinct", Sound;  "(wr ?p ?v (wr ?q ?u ?h))"       => "(wr ?q ?u (wr ?p ?v ?h))"
+                  if are_distinct_paths("?p", "?q")),
+            rule!("wr-rd-same", Sound;           "(wr ?p (rd ?p ?h) ?h)"          => "?h"),
+        ],
+        "loop" => vec![
+            // Loops. A theta's initial value is evaluated before the loop, so a
+            // theta that steps to its initial value is loop invariant
+            rule!("theta-invariant", Sound;  "(theta ?a ?a)"              => "?a"),
+            // A theta that steps to itself never changes (the loop doesn't assign
+            // its variable), and neither do method parameters or the unit status
+            // of a heap that doesn't throw
+            rule!("theta-self", Sound;       "(theta ?a ?b)"              => "?a" if is_matched_class("?b")),
+            rule!("eval-var", Sound;         "(eval ?a ?i)"               => "?a" if is_var("?a")),
+            rule!("eval-unit", Sound;        "(eval unit ?i)"             => "unit"),
+            // Thetas and passes don't say which loop they belong to, so this
+            // can't tell whether the theta is one of the eval's loop or, say, of
+            // a loop nested in it
+            rule!("eval-theta-zero", Unsound; "(eval (theta ?a ?b) 0)"   => "?a"),
+            // Operators other than `&&`, `||` and `phi` evaluate all their
+            // operands, so they apply pointwise to the sequences a loop computes,
+            // and evaluating the result at a pass evaluates each operand there,
+            // whichever loop the operands come from
+            rule!("eval-add", Sound;         "(eval (+ ?a ?b) ?i)"        => "(+ (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-sub", Sound;         "(eval (- ?a ?b) ?i)"        => "(- (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-mul", Sound;         "(eval (* ?a ?b) ?i)"        => "(* (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-neg", Sound;         "(eval (--- ?a) ?i)"         => "(--- (eval ?a ?i))"),
+            rule!("eval-not", Sound;         "(eval (! ?a) ?i)"           => "(! (eval ?a ?i))"),
+            rule!("eval-eq", Sound;          "(eval (== ?a ?b) ?i)"       => "(== (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-lt", Sound;          "(eval (< ?a ?b) ?i)"        => "(< (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-lte", Sound;         "(eval (<= ?a ?b) ?i)"       => "(<= (eval ?a ?i) (eval ?b ?i))"),
+            rule!("eval-phi", Sound;         "(eval (phi ?c ?a ?b) ?i)"   => "(phi (eval ?c ?i) (eval ?a ?i) (eval ?b ?i))"),
+            // Pushing operators into thetas. Pairing two thetas assumes they step
+            // together, in the same loop, which the patterns can't check;
+            // `theta-neg` is tagged along with them until thetas record their loop
+            rule!("theta-pair-add", Unsound; "(+ (theta ?a ?b) (theta ?c ?d))" => "(theta (+ ?a ?c) (+ ?b ?d))"),
+            rule!("theta-pair-sub", Unsound; "(- (theta ?a ?b) (theta ?c ?d))" => "(theta (- ?a ?c) (- ?b ?d))"),
+            rule!("theta-pair-mul", Unsound; "(* (theta ?a ?b) (theta ?c ?d))" => "(theta (* ?a ?c) (* ?b ?d))"),
+            rule!("theta-neg", Unsound;      "(--- (theta ?a ?b))"        => "(theta (--- ?a) (--- ?b))"),
+        ],
+        "call" => vec![
+            // Calls. A pure call's value doesn't depend on the heap, and it leaves
+            // the heap as it found it. Boxing and then unboxing is the identity,
+            // but the boxing `valueOf`s also parse `String`s and widen narrower
+            // types (rounding, for `Float.valueOf` of an `int`), so the value
+            // boxed must be known to have the box's type. Booleans aren't tracked,
+            // so `Boolean.valueOf` could be given a `String`
+            rule!("invoke-static-pure", Sound;
+                  "(invoke->peg (invoke-static ?h ?m ?a))" => "(pure-call ?m unit ?a)"
+                  if is_pure_static_call(purity, "?m", "?a")),
+            rule!("invoke-static-pure-heap", Sound;
+                  "(heap (invoke->heap-state (invoke-static ?h ?m ?a)) (invoke->exception-status (invoke-static ?h ?m ?a)))"
+                  => "?h" if is_pure_static_call(purity, "?m", "?a")),
+            rule!("invoke-pure", Sound;
+                  "(invoke->peg (invoke ?h ?r ?m ?a))" => "(pure-call ?m ?r ?a)"
+                  if is_pure_instance_call(purity, "?r", "?m")),
+            rule!("invoke-pure-heap", Sound;
+                  "(heap (invoke->heap-state (invoke ?h ?r ?m ?a)) (invoke->exception-status (invoke ?h ?r ?m ?a)))"
+                  => "?h" if is_pure_instance_call(purity, "?r", "?m")),
+            rule!("unbox-int", Sound;       "(pure-call intValue (pure-call (method Integer.valueOf) unit (actuals ?x)) (actuals))"     => "?x" if has_type("?x", NumericType::Int)),
+            rule!("unbox-long", Sound;      "(pure-call longValue (pure-call (method Long.valueOf) unit (actuals ?x)) (actuals))"       => "?x" if has_type("?x", NumericType::Long)),
+            rule!("unbox-short", Sound;     "(pure-call shortValue (pure-call (method Short.valueOf) unit (actuals ?x)) (actuals))"     => "?x" if has_type("?x", NumericType::Short)),
+            rule!("unbox-byte", Sound;      "(pure-call byteValue (pure-call (method Byte.valueOf) unit (actuals ?x)) (actuals))"       => "?x" if has_type("?x", NumericType::Byte)),
+            rule!("unbox-char", Sound;      "(pure-call charValue (pure-call (method Character.valueOf) unit (actuals ?x)) (actuals))"  => "?x"),
+            rule!("unbox-boolean", Unsound; "(pure-call booleanValue (pure-call (method Boolean.valueOf) unit (actuals ?x)) (actuals))" => "?x"),
+            rule!("unbox-float", Sound;     "(pure-call floatValue (pure-call (method Float.valueOf) unit (actuals ?x)) (actuals))"     => "?x" if has_type("?x", NumericType::Float)),
+            rule!("unbox-double", Sound;    "(pure-call doubleValue (pure-call (method Double.valueOf) unit (actuals ?x)) (actuals))"   => "?x" if has_type("?x", NumericType::Double)),
+        ],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether saturating `expr` with the built-in rules puts it in the class of
+    /// `other`
+    fn rewrites_to(expr: &str, other: &str) -> bool {
+        let mut egraph = EGraph::default();
+        let root = egraph.add_expr(&expr.parse().unwrap());
+        let other = egraph.add_expr(&other.parse().unwrap());
+        let runner = egg::Runner::default().with_egraph(egraph).with_iter_limit(10).run(&*rw_rules());
+        runner.egraph.find(root) == runner.egraph.find(other)
+    }
+
+    /// Like `rewrites_to`, with only the rules tagged `Sound`
+    fn soundly_rewrites_to(expr: &str, other: &str) -> bool {
+        let rules: Vec<Rewrite<Peg, PegAnalysis>> = rules().into_iter()
+            .filter(|rule| rule.soundness == Soundness::Sound)
+            .map(|rule| rule.rewrite)
+            .collect();
+        let mut egraph = EGraph::default();
+        let root = egraph.add_expr(&expr.parse().unwrap());
+        let other = egraph.add_expr(&other.parse().unwrap());
+        let runner = egg::Runner::default().with_egraph(egraph).with_iter_limit(10).run(&rules);
+        runner.egraph.find(root) == runner.egraph.find(other)
+    }
+
+    #[test]
+    fn evals_distribute_soundly_over_pointwise_operators() {
+        let sum = "(eval (+ (theta (var a \"int\") s) (theta 1 t)) (pass c))";
+        let distributed = "(+ (eval (theta (var a \"int\") s) (pass c)) (eval (theta 1 t) (pass c)))";
+        assert!(soundly_rewrites_to(sum, distributed));
+        assert!(soundly_rewrites_to("(eval (! (theta x y)) (pass c))", "(! (eval (theta x y) (pass c)))"));
+    }
+
+    #[test]
+    fn evals_of_thetas_need_their_loop() {
+        // The theta may be of a loop nested in the eval's, whose first value
+        // differs from pass to pass
+        assert!(!soundly_rewrites_to("(eval (theta (var a \"int\") s) 0)", "(var a \"int\")"));
+        assert!(rewrites_to("(eval (theta (var a \"int\") s) 0)", "(var a \"int\")"));
+        assert!(!soundly_rewrites_to("(+ (theta 0 s) (theta 1 t))", "(theta (+ 0 1) (+ s t))"));
+    }
+
+    const BOXED: &str = "(pure-call (method Integer.valueOf) unit (actuals (var x \"int\")))";
+
+    #[test]
+    fn concatenation_does_not_commute() {
+        assert!(!rewrites_to("(+ \"a\" (var x \"int\"))", "(+ (var x \"int\") \"a\")"));
+        assert!(!rewrites_to("(+ (var s \"java.lang.String\") (var t \"java.lang.String\"))",
+                             "(+ (var t \"java.lang.String\") (var s \"java.lang.String\"))"));
+        assert!(rewrites_to("(+ (var x \"int\") (var y \"double\"))", "(+ (var y \"double\") (var x \"int\"))"));
+    }
+
+    #[test]
+    fn boxed_values_are_not_identical() {
+        assert!(!rewrites_to(&format!("(== {} {})", BOXED, BOXED), "true"));
+        assert!(!rewrites_to(&format!("(!= {} {})", BOXED, BOXED), "false"));
+        assert!(rewrites_to("(== (var x \"int\") (var x \"int\"))", "true"));
+    }
+
+    #[test]
+    fn unboxing_needs_the_boxed_type() {
+        assert!(rewrites_to(&format!("(pure-call intValue {} (actuals))", BOXED), "(var x \"int\")"));
+        let unbox = |method: &str, class: &str, x: &str| {
+            rewrites_to(&format!("(pure-call {} (pure-call (method {}.valueOf) unit (actuals {})) (actuals))", method, class, x), x)
+        };
+        assert!(!unbox("intValue", "Integer", "(var s nil)"));
+        assert!(!unbox("intValue", "Integer", "\"12\""));
+        assert!(!unbox("longValue", "Long", "(var x \"int\")"));
+        assert!(!unbox("floatValue", "Float", "(var x \"int\")"));
+        assert!(unbox("floatValue", "Float", "(var x \"float\")"));
+        assert!(unbox("byteValue", "Byte", "(var x \"byte\")"));
+    }
+
+    #[test]
+    fn value_of_is_only_pure_for_primitive_arguments() {
+        let call = |heap: &str, class: &str, x: &str| {
+            format!("(invoke-static {} (method {}.valueOf) (actuals {}))", heap, class, x)
+        };
+        // Values of calls in different heaps merge when the calls are pure
+        let merged = |class: &str, x: &str| {
+            rewrites_to(&format!("(invoke->peg {})", call("h1", class, x)), &format!("(invoke->peg {})", call("h2", class, x)))
+        };
+        let heap_kept = |class: &str, x: &str| {
+            let call = call("h", class, x);
+            rewrites_to(&format!("(heap (invoke->heap-state {}) (invoke->exception-status {}))", call, call), "h")
+        };
+        assert!(merged("String", "(var x \"int\")"));
+        assert!(merged("Boolean", "true"));
+        assert!(heap_kept("Integer", "(var x \"int\")"));
+        for (class, x) in &[
+            ("String", "(var o \"java.lang.Object\")"),
+            ("String", "(var o nil)"),
+            ("Integer", "(var s \"java.lang.String\")"),
+            ("Integer", "\"12\""),
+            ("Long", "(var s nil)"),
+        ] {
+            assert!(!merged(class, x), "{}.valueOf({}) was merged", class, x);
+            assert!(!heap_kept(class, x), "{}.valueOf({}) kept the heap", class, x);
+        }
+    }
 }
@@ -0,0 +1,264 @@
+use crate::peg::*;
+use crate::rewrites::{all_of, Rule, RuleCondition, Soundness};
+use egg::{rewrite as rw, Pattern, Var};
+use std::fs;
+
+/// One of the conditions a rule file may guard a rule with
+#[derive(Clone, Debug)]
+pub enum ConditionSpec {
+    IsConst(String),
+    IsNotConst(String),
+    IsVar(String),
+    IsNotSameVar(String, String),
+}
+
+impl ConditionSpec {
+    fn parse(name: &str, args: &[&str]) -> Result<Self, String> {
+        match (name, args) {
+            ("is_const", [a]) => Ok(ConditionSpec::IsConst(a.to_string())),
+            ("is_not_const", [a]) => Ok(ConditionSpec::IsNotConst(a.to_string())),
+            ("is_var", [a]) => Ok(ConditionSpec::IsVar(a.to_string())),
+            ("is_not_same_var", [a, b]) => Ok(ConditionSpec::IsNotSameVar(a.to_string(), b.to_string())),
+            ("is_const", _) | ("is_not_const", _) | ("is_var", _) | ("is_not_same_var", _) => {
+                Err(format!("wrong number of arguments to condition '{}'", name))
+            }
+            _ => Err(format!(
+                "unknown condition '{}' (expected is_const, is_not_const, is_var or is_not_same_var)",
+                name
+            )),
+        }
+    }
+
+    fn vars(&self) -> Vec<&str> {
+        match self {
+            ConditionSpec::IsConst(a) | ConditionSpec::IsNotConst(a) | ConditionSpec::IsVar(a) => vec![a.as_str()],
+            ConditionSpec::IsNotSameVar(a, b) => vec![a.as_str(), b.as_str()],
+        }
+    }
+
+    fn to_condition(&self) -> RuleCondition {
+        match self {
+            ConditionSpec::IsConst(a) => Box::new(is_const(a)),
+            ConditionSpec::IsNotConst(a) => Box::new(is_not_const(a)),
+            ConditionSpec::IsVar(a) => Box::new(is_var(a)),
+            ConditionSpec::IsNotSameVar(a, b) => Box::new(is_not_same_var(a, b)),
+        }
+    }
+}
+
+/// A rule read from a rule file. Specs, unlike `Rule`s, can be shared between
+/// threads, and each worker builds its own rules from them
+#[derive(Clone, Debug)]
+pub struct RuleSpec {
+    pub name: String,
+    pub lhs: String,
+    pub rhs: String,
+    pub conditions: Vec<ConditionSpec>,
+    pub soundness: Soundness,
+}
+
+impl RuleSpec {
+    pub fn to_rule(&self) -> Rule {
+        // Both patterns were validated by `parse_rule`
+        let lhs: Pattern<Peg> = self.lhs.parse().unwrap();
+        let rhs: Pattern<Peg> = self.rhs.parse().unwrap();
+        let condition = || all_of(self.conditions.iter().map(ConditionSpec::to_condition).collect());
+        Rule {
+            rewrite: if self.conditions.is_empty() {
+                rw!(self.name.as_str(); lhs => rhs)
+            } else {
+                rw!(self.name.as_str(); lhs => rhs if condition())
+            },
+            lhs: self.lhs.clone(),
+            rhs: self.rhs.clone(),
+            condition: if self.conditions.is_empty() { None } else { Some(Box::new(condition())) },
+            soundness: self.soundness,
+        }
+    }
+}
+
+/// Read the rules in `file`, one per line:
+///
+/// ```text
+/// NAME sound|unsound: LHS => RHS [if CONDITION(?a), ...]
+/// ```
+///
+/// where `LHS` and `RHS` are patterns over the `Peg` language, and each
+/// `CONDITION` is one of `is_const`, `is_not_const`, `is_var` (taking one
+/// variable) and `is_not_same_var` (taking two). Blank lines and lines starting
+/// with `#` are ignored.
+pub fn load(file: &str) -> Result<Vec<RuleSpec>, String> {
+    let contents = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read rule file '{}': {}", file, e))?;
+    let mut specs: Vec<RuleSpec> = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let spec = parse_rule(line).map_err(|e| format!("{}:{}: {}", file, index + 1, e))?;
+        if specs.iter().any(|other| other.name == spec.name) {
+            return Err(format!("{}:{}: duplicate rule name '{}'", file, index + 1, spec.name));
+        }
+        specs.push(spec);
+    }
+    Ok(specs)
+}
+
+fn parse_rule(line: &str) -> Result<RuleSpec, String> {
+    const EXPECTED: &str = "expected 'NAME sound|unsound: LHS => RHS'";
+    // Names hold no whitespace, but they, and patterns (in string literals), may
+    // hold colons, so the colon that ends the head is the one after the tag
+    let (name, rest) = line.split_once(char::is_whitespace).ok_or(EXPECTED)?;
+    let (tag, body) = rest.split_once(':').ok_or(EXPECTED)?;
+    let soundness = match tag.trim() {
+        "sound" => Soundness::Sound,
+        "unsound" => Soundness::Unsound,
+        tag => return Err(format!("expected a rule name and 'sound' or 'unsound', found '{} {}'", name, tag)),
+    };
+    let name = name.to_string();
+    let (rewrite, conditions) = match body.split_once(" if ") {
+        Some((rewrite, conditions)) => (rewrite, parse_conditions(conditions)?),
+        None => (body, vec![]),
+    };
+    let (lhs, rhs) = rewrite.split_once("=>").ok_or("expected 'LHS => RHS'")?;
+    let (lhs, rhs) = (lhs.trim(), rhs.trim());
+
+    let lhs_vars = pattern_vars(lhs).map_err(|e| format!("bad left hand side '{}': {}", lhs, e))?;
+    let rhs_vars = pattern_vars(rhs).map_err(|e| format!("bad right hand side '{}': {}", rhs, e))?;
+    for var in &rhs_vars {
+        if !lhs_vars.contains(var) {
+            return Err(format!("{} is used on the right hand side but not bound by the left", var));
+        }
+    }
+    for condition in &conditions {
+        for var in condition.vars() {
+            let bound = var.parse::<Var>().map_or(false, |var| lhs_vars.contains(&var));
+            if !bound {
+                return Err(format!("condition variable {} is not bound by the left hand side", var));
+            }
+        }
+    }
+
+    Ok(RuleSpec { name, lhs: lhs.to_string(), rhs: rhs.to_string(), conditions, soundness })
+}
+
+/// The variables of `pattern`, after checking that it parses. Operators of the
+/// wrong arity, or unknown operators with children, don't
+fn pattern_vars(pattern: &str) -> Result<Vec<Var>, String> {
+    pattern.parse::<Pattern<Peg>>().map(|pattern| pattern.vars()).map_err(|e| e.to_string())
+}
+
+/// Parse `cond(?a), cond(?a, ?b), ...`
+fn parse_conditions(text: &str) -> Result<Vec<ConditionSpec>, String> {
+    let mut conditions = vec![];
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let open = rest.find('(').ok_or_else(|| format!("expected CONDITION(...), found '{}'", rest))?;
+        let close = rest.find(')').ok_or_else(|| format!("unclosed condition '{}'", rest))?;
+        if close < open {
+            return Err(format!("expected CONDITION(...), found '{}'", rest));
+        }
+        let args: Vec<&str> = rest[open + 1..close].split(',').map(str::trim).collect();
+        conditions.push(ConditionSpec::parse(rest[..open].trim(), &args)?);
+        rest = rest[close + 1..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(line: &str) -> String {
+        match parse_rule(line) {
+            Err(e) => e,
+            Ok(spec) => panic!("expected an error parsing {}, got {:?}", line, spec),
+        }
+    }
+
+    #[test]
+    fn parses_rules_with_and_without_conditions() {
+        let spec = parse_rule("add-zero sound: (+ ?a 0) => ?a").unwrap();
+        assert_eq!((spec.name.as_str(), spec.lhs.as_str(), spec.rhs.as_str()), ("add-zero", "(+ ?a 0)", "?a"));
+        assert_eq!(spec.soundness, Soundness::Sound);
+        assert!(spec.conditions.is_empty());
+
+        let spec = parse_rule("sub-self unsound: (- ?a ?b) => 0 if is_var(?a), is_not_same_var(?a, ?b)").unwrap();
+        assert_eq!(spec.soundness, Soundness::Unsound);
+        assert_eq!(spec.rhs, "0");
+        assert_eq!(spec.conditions.len(), 2);
+    }
+
+    #[test]
+    fn splits_the_head_at_the_colon_after_the_tag() {
+        let spec = parse_rule("ns:fold sound: (+ ?a 0) => ?a").unwrap();
+        assert_eq!(spec.name, "ns:fold");
+        let spec = parse_rule("concat sound : (+ \"a:\" ?a) => (+ \"a:\" ?a)").unwrap();
+        assert_eq!(spec.lhs, "(+ \"a:\" ?a)");
+    }
+
+    #[test]
+    fn rejects_malformed_heads() {
+        assert_eq!(parse_error("add-zero"), "expected 'NAME sound|unsound: LHS => RHS'");
+        assert_eq!(parse_error("add-zero sound (+ ?a 0) => ?a"), "expected 'NAME sound|unsound: LHS => RHS'");
+        assert_eq!(
+            parse_error("add-zero maybe: (+ ?a 0) => ?a"),
+            "expected a rule name and 'sound' or 'unsound', found 'add-zero maybe'"
+        );
+        assert_eq!(parse_error("add-zero sound: (+ ?a 0)"), "expected 'LHS => RHS'");
+    }
+
+    #[test]
+    fn rejects_bad_patterns_and_unbound_variables() {
+        assert!(parse_error("r sound: (+ ?a) => ?a").starts_with("bad left hand side '(+ ?a)': "));
+        assert!(parse_error("r sound: ?a => (- ?a)").starts_with("bad right hand side '(- ?a)': "));
+        assert_eq!(
+            parse_error("r sound: (+ ?a 0) => ?b"),
+            "?b is used on the right hand side but not bound by the left"
+        );
+        assert_eq!(
+            parse_error("r sound: (+ ?a 0) => ?a if is_var(?b)"),
+            "condition variable ?b is not bound by the left hand side"
+        );
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let conditions = parse_conditions("is_const(?a),is_not_const( ?b ) , is_not_same_var(?a, ?b)").unwrap();
+        let vars: Vec<Vec<&str>> = conditions.iter().map(ConditionSpec::vars).collect();
+        assert_eq!(vars, vec![vec!["?a"], vec!["?b"], vec!["?a", "?b"]]);
+        assert!(parse_conditions("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        let error = |text: &str| parse_conditions(text).unwrap_err();
+        assert_eq!(error("is_const"), "expected CONDITION(...), found 'is_const'");
+        assert_eq!(error("is_const(?a"), "unclosed condition 'is_const(?a'");
+        assert_eq!(error("is_const)(?a"), "expected CONDITION(...), found 'is_const)(?a'");
+        assert_eq!(error("is_var(?a, ?b)"), "wrong number of arguments to condition 'is_var'");
+        assert_eq!(
+            error("is_pure(?a)"),
+            "unknown condition 'is_pure' (expected is_const, is_not_const, is_var or is_not_same_var)"
+        );
+    }
+
+    #[test]
+    fn loads_files_skipping_comments_and_rejecting_duplicates() {
+        let file = std::env::temp_dir().join("rule_file_tests.rules");
+        let path = file.to_str().unwrap();
+        fs::write(&file, "# comment\n\nr sound: (+ ?a 0) => ?a\nr unsound: (* ?a 1) => ?a\n").unwrap();
+        assert_eq!(load(path).unwrap_err(), format!("{}:4: duplicate rule name 'r'", path));
+        fs::write(&file, "# comment\n\nr sound: (+ ?a 0) => ?a\ns unsound: (* ?a 1) => ?a\n").unwrap();
+        let names: Vec<String> = load(path).unwrap().into_iter().map(|spec| spec.name).collect();
+        assert_eq!(names, vec!["r", "s"]);
+        fs::write(&file, "r sound: (+ ?a 0) => ?c\n").unwrap();
+        assert_eq!(
+            load(path).unwrap_err(),
+            format!("{}:1: ?c is used on the right hand side but not bound by the left", path)
+        );
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::mutants_log::Breakdown;
 use egg::StopReason;
 
 #[derive(Default, Debug)]
@@ -11,6 +12,14 @@ pub struct RuntimeMetrics {
     pub total_subject_files: u32,
     pub total_mutants: u32,
     pub total_discovered_equivalences: u32,
+    /// Mutants equivalent to their original
+    pub total_equivalent_mutants: u32,
+    /// Mutants equivalent to a smaller mutant, but not to their original
+    pub total_duplicate_mutants: u32,
+    /// Mutants not subsumed by any other mutant of their subject
+    pub total_dominators: u32,
+    /// Mutants and equivalences by operator, method and line, with a mutants log
+    pub breakdown: Breakdown,
 }
 
 impl RuntimeMetrics {
@@ -31,6 +40,15 @@ impl RuntimeMetrics {
         self.total_discovered_equivalences += new_equivalences;
     }
 
+    pub fn record_equivalent_and_duplicates(&mut self, equivalent: u32, duplicates: u32) {
+        self.total_equivalent_mutants += equivalent;
+        self.total_duplicate_mutants += duplicates;
+    }
+
+    pub fn record_dominators(&mut self, dominators: u32) {
+        self.total_dominators += dominators;
+    }
+
     pub fn record_new_subjects(&mut self, new_subjects: u32) {
         self.total_subjects += new_subjects;
     }
@@ -42,6 +60,23 @@ impl RuntimeMetrics {
     pub fn record_new_mutants(&mut self, new_mutants: u32) {
         self.total_mutants += new_mutants;
     }
+
+    /// Add the counts gathered by another worker to these
+    pub fn merge(&mut self, other: RuntimeMetrics) {
+        self.max_iterations_count += other.max_iterations_count;
+        self.execution_time_limit += other.execution_time_limit;
+        self.max_nodes_count += other.max_nodes_count;
+        self.saturation_events += other.saturation_events;
+        self.other_events += other.other_events;
+        self.total_subjects += other.total_subjects;
+        self.total_subject_files += other.total_subject_files;
+        self.total_mutants += other.total_mutants;
+        self.total_discovered_equivalences += other.total_discovered_equivalences;
+        self.total_equivalent_mutants += other.total_equivalent_mutants;
+        self.total_duplicate_mutants += other.total_duplicate_mutants;
+        self.total_dominators += other.total_dominators;
+        self.breakdown.merge(other.breakdown);
+    }
 }
 
 impl ToString for RuntimeMetrics {
@@ -56,6 +91,9 @@ Total Subject Files: {}
 Total Subjects: {}
 Total Mutants: {}
 Total Discovered Equivalences: {}
+Total Equivalent Mutants: {}
+Total Duplicate Mutants: {}
+Total Dominator Mutants: {}
 ",
             self.max_iterations_count,
             self.execution_time_limit,
@@ -65,7 +103,10 @@ Total Discovered Equivalences: {}
             self.total_subject_files,
             self.total_subjects,
             self.total_mutants,
-            self.total_discovered_equivalences
+            self.total_discovered_equivalences,
+            self.total_equivalent_mutants,
+            self.total_duplicate_mutants,
+            self.total_dominators
         )
     }
 }
@@ -0,0 +1,326 @@
+use crate::driver::Subject;
+use crate::peg::{EGraph, Peg};
+use egg::{Id, Language};
+use std::collections::{HashMap, HashSet};
+
+/// The outcomes of comparing two operands. `UNORDERED` is a comparison with a
+/// NaN, for which every operator but `!=` is false
+const LT: u8 = 0b0001;
+const EQ: u8 = 0b0010;
+const GT: u8 = 0b0100;
+const UNORDERED: u8 = 0b1000;
+const ORDERED: u8 = LT | EQ | GT;
+const ALL: u8 = ORDERED | UNORDERED;
+
+/// Where a mutant's PEG first differs from its subject's: walking down from
+/// both roots through nodes that differ in a single child, the last pair of
+/// e-classes reached
+#[derive(Clone, Debug)]
+pub struct MutationPoint {
+    /// The e-classes of the subject's PEG above the point, each with the index
+    /// of the child walked into
+    pub path: Vec<(Id, usize)>,
+    pub original: Id,
+    pub mutant: Id,
+}
+
+/// A mutation point, with the (ordered) operands of the comparison there
+type PointKey = (Vec<(Id, usize)>, Id, Id, Id);
+
+/// The subsumption relation between the mutants of a subject: mutant `m1`
+/// subsumes `m2` when every test that kills `m1` also kills `m2`.
+///
+/// Only mutants of the same comparison are related. There, each mutant is
+/// killed (in the weak mutation sense) by the outcomes of comparing the
+/// operands for which it disagrees with the subject, and `m1` subsumes `m2`
+/// when `m1`'s outcomes are a subset of `m2`'s. Operands are assumed to take
+/// every ordering their ranges allow.
+#[derive(Clone, Debug, Default)]
+pub struct SubsumptionGraph {
+    /// Groups of mutants that subsume each other, each sorted
+    pub nodes: Vec<Vec<u32>>,
+    /// `(i, j)` when the mutants of `nodes[i]` subsume those of `nodes[j]`,
+    /// leaving out the edges implied by transitivity
+    pub edges: Vec<(usize, usize)>,
+    /// The smallest mutant of each node that no other node subsumes: the
+    /// mutants a test suite needs to kill to kill all of them
+    pub dominators: Vec<u32>,
+}
+
+/// The mutation point of each of `subj`'s mutants in `egraph`, which must not
+/// have been saturated yet: afterwards, the classes along the way hold too many
+/// nodes to tell which of them the mutation changed
+pub fn mutation_points(
+    subj: &Subject,
+    egraph: &EGraph,
+    raw_id_to_egg_id: &HashMap<Id, Id>,
+) -> HashMap<u32, MutationPoint> {
+    let lookup = |pid: &str| {
+        pid.parse::<usize>().ok().and_then(|raw_id| raw_id_to_egg_id.get(&Id::from(raw_id)).copied())
+    };
+    let root = match lookup(&subj.pid) {
+        Some(root) => root,
+        None => return HashMap::new(),
+    };
+    subj.mutants.iter()
+        .filter_map(|mutant| Some((mutant.mid, find_point(egraph, root, lookup(&mutant.pid)?)?)))
+        .collect()
+}
+
+/// The mutation point of the mutant rooted at `m` in the subject rooted at `o`,
+/// unless they are the same class, or the walk goes around a loop
+fn find_point(egraph: &EGraph, o: Id, m: Id) -> Option<MutationPoint> {
+    let mut path = vec![];
+    let mut visited = HashSet::new();
+    let (mut o, mut m) = (egraph.find(o), egraph.find(m));
+    loop {
+        if o == m || !visited.insert((o, m)) {
+            return None;
+        }
+        match differing_child(egraph, o, m) {
+            Some((index, o_child, m_child)) => {
+                path.push((o, index));
+                o = egraph.find(o_child);
+                m = egraph.find(m_child);
+            }
+            None => return Some(MutationPoint { path, original: o, mutant: m }),
+        }
+    }
+}
+
+/// The index and classes of the only child in which a node of class `o`
+/// differs from a node of class `m` with the same operator
+fn differing_child(egraph: &EGraph, o: Id, m: Id) -> Option<(usize, Id, Id)> {
+    egraph[o].nodes.iter().find_map(|o_node| egraph[m].nodes.iter().find_map(|m_node| {
+        if !o_node.matches(m_node) {
+            return None;
+        }
+        let mut differing = o_node.children().iter()
+            .zip(m_node.children())
+            .enumerate()
+            .filter(|(_, (c, d))| egraph.find(**c) != egraph.find(**d));
+        match (differing.next(), differing.next()) {
+            (Some((index, (c, d))), None) => Some((index, *c, *d)),
+            _ => None,
+        }
+    }))
+}
+
+/// The operands of a comparison, and the outcomes for which it holds
+fn comparison(node: &Peg) -> Option<(Id, Id, u8)> {
+    match node {
+        Peg::Lt([a, b]) => Some((*a, *b, LT)),
+        Peg::Lte([a, b]) => Some((*a, *b, LT | EQ)),
+        Peg::Gt([a, b]) => Some((*a, *b, GT)),
+        Peg::Gte([a, b]) => Some((*a, *b, GT | EQ)),
+        Peg::Equ([a, b]) => Some((*a, *b, EQ)),
+        Peg::Neq([a, b]) => Some((*a, *b, LT | GT | UNORDERED)),
+        _ => None,
+    }
+}
+
+/// The outcomes of comparing `b` with `a`, given those of comparing `a` with `b`
+fn swap(outcomes: u8) -> u8 {
+    (outcomes & (EQ | UNORDERED)) | ((outcomes & LT) << 2) | ((outcomes & GT) >> 2)
+}
+
+/// The outcomes of comparing `a` with `b` their ranges allow
+fn feasible(egraph: &EGraph, a: Id, b: Id) -> u8 {
+    match (egraph[a].data.range, egraph[b].data.range) {
+        (Some(a), Some(b)) => {
+            let lt = if a.lo < b.hi { LT } else { 0 };
+            let eq = if a.lo <= b.hi && b.lo <= a.hi { EQ } else { 0 };
+            let gt = if a.hi > b.lo { GT } else { 0 };
+            lt | eq | gt
+        }
+        _ => ALL,
+    }
+}
+
+/// The outcomes of comparing `a` with `b` for which the mutant class `m`
+/// holds, when it replaces the comparison `o` that holds for `holds`: another
+/// comparison of the same operands, a constant, or the negation of `o`
+fn mutant_outcomes(egraph: &EGraph, o: Id, m: Id, a: Id, b: Id, holds: u8) -> Option<u8> {
+    if let Some(Peg::Bool(value)) = egraph[m].data.constant {
+        return Some(if value { ALL } else { 0 });
+    }
+    egraph[m].nodes.iter().find_map(|node| match node {
+        Peg::Not(x) if egraph.find(*x) == o => Some(ALL ^ holds),
+        _ => {
+            let (c, d, outcomes) = comparison(node)?;
+            match (egraph.find(c), egraph.find(d)) {
+                (c, d) if (c, d) == (a, b) => Some(outcomes),
+                (c, d) if (c, d) == (b, a) => Some(swap(outcomes)),
+                _ => None,
+            }
+        }
+    })
+}
+
+/// The comparison at `point` in the saturated `egraph`, and the outcomes for
+/// which the mutant disagrees with the subject there
+fn failure(egraph: &EGraph, point: &MutationPoint) -> Option<(PointKey, u8)> {
+    let (o, m) = (egraph.find(point.original), egraph.find(point.mutant));
+    if o == m {
+        return None;
+    }
+    egraph[o].nodes.iter().find_map(|node| {
+        let (a, b, holds) = comparison(node)?;
+        let (a, b) = (egraph.find(a), egraph.find(b));
+        let (a, b, holds) = if a <= b { (a, b, holds) } else { (b, a, swap(holds)) };
+        let mutant_holds = mutant_outcomes(egraph, o, m, a, b, holds)?;
+        let path = point.path.iter().map(|(id, index)| (egraph.find(*id), *index)).collect();
+        Some(((path, o, a, b), (holds ^ mutant_holds) & feasible(egraph, a, b)))
+    })
+}
+
+/// The subsumption graph of the mutants in `classes`, a subject's equivalence
+/// classes (where `0` stands for the subject) in the saturated `egraph`.
+/// Mutants equivalent to the subject are left out
+pub fn subsumption_graph(
+    egraph: &EGraph,
+    classes: &[HashSet<u32>],
+    points: &HashMap<u32, MutationPoint>,
+) -> SubsumptionGraph {
+    // Mutants that are killed by the same outcomes subsume each other. Those
+    // killed by no ordered outcome may well be equivalent, and are kept apart
+    let mut nodes: Vec<(Vec<u32>, Option<(PointKey, u8)>)> = vec![];
+    for class in classes.iter().filter(|class| !class.contains(&0)) {
+        let mut mids: Vec<u32> = class.iter().copied().collect();
+        mids.sort();
+        let killed_by = mids.iter().find_map(|mid| failure(egraph, points.get(mid)?));
+        let duplicate = killed_by.as_ref()
+            .filter(|(_, outcomes)| outcomes & ORDERED != 0)
+            .and_then(|killed_by| nodes.iter().position(|(_, other)| other.as_ref() == Some(killed_by)));
+        match duplicate {
+            Some(index) => {
+                nodes[index].0.extend(mids);
+                nodes[index].0.sort();
+            }
+            None => nodes.push((mids, killed_by)),
+        }
+    }
+
+    let subsumes = |i: usize, j: usize| match (&nodes[i].1, &nodes[j].1) {
+        (Some((key1, outcomes1)), Some((key2, outcomes2))) => {
+            i != j && key1 == key2 && outcomes1 & ORDERED != 0 && outcomes1 & !outcomes2 == 0
+        }
+        _ => false,
+    };
+    let n = nodes.len();
+    let edges = (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        .filter(|&(i, j)| subsumes(i, j) && !(0..n).any(|k| subsumes(i, k) && subsumes(k, j)))
+        .collect();
+    let dominators = (0..n)
+        .filter(|&j| !(0..n).any(|i| subsumes(i, j)))
+        .map(|j| nodes[j].0[0])
+        .collect();
+
+    SubsumptionGraph {
+        nodes: nodes.into_iter().map(|(mids, _)| mids).collect(),
+        edges,
+        dominators,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(egraph: &mut EGraph, expr: &str) -> Id {
+        egraph.add_expr(&expr.parse().unwrap())
+    }
+
+    #[test]
+    fn swapping_exchanges_lt_and_gt() {
+        assert_eq!(swap(LT), GT);
+        assert_eq!(swap(GT), LT);
+        assert_eq!(swap(LT | EQ), GT | EQ);
+        assert_eq!(swap(EQ | UNORDERED), EQ | UNORDERED);
+        for outcomes in 0..=ALL {
+            assert_eq!(swap(swap(outcomes)), outcomes);
+        }
+    }
+
+    #[test]
+    fn comparisons_hold_for_their_outcomes() {
+        let (a, b) = (Id::from(0), Id::from(1));
+        let outcomes = |node: Peg| comparison(&node).unwrap().2;
+        assert_eq!(outcomes(Peg::Lt([a, b])), swap(outcomes(Peg::Gt([a, b]))));
+        assert_eq!(outcomes(Peg::Lte([a, b])), swap(outcomes(Peg::Gte([a, b]))));
+        assert_eq!(outcomes(Peg::Equ([a, b])), EQ);
+        // NaN is unequal to everything, and neither less nor greater
+        assert_eq!(outcomes(Peg::Neq([a, b])), ALL ^ EQ);
+        assert_eq!(outcomes(Peg::Lte([a, b])) & UNORDERED, 0);
+        assert!(comparison(&Peg::Add([a, b])).is_none());
+    }
+
+    #[test]
+    fn ranges_bound_the_feasible_outcomes() {
+        let mut egraph = EGraph::default();
+        let one = add(&mut egraph, "1");
+        let two = add(&mut egraph, "2");
+        let c = add(&mut egraph, "(var c \"char\")");
+        let minus_one = add(&mut egraph, "-1");
+        let x = add(&mut egraph, "(var x \"int\")");
+        let y = add(&mut egraph, "(var y \"int\")");
+        let d = add(&mut egraph, "(var d \"double\")");
+        egraph.rebuild();
+        assert_eq!(feasible(&egraph, one, two), LT);
+        assert_eq!(feasible(&egraph, two, one), GT);
+        assert_eq!(feasible(&egraph, one, one), EQ);
+        assert_eq!(feasible(&egraph, c, minus_one), GT);
+        assert_eq!(feasible(&egraph, x, y), ORDERED);
+        assert_eq!(feasible(&egraph, d, x), ALL);
+    }
+
+    #[test]
+    fn mutation_points_follow_the_differing_child() {
+        let mut egraph = EGraph::default();
+        let original = add(&mut egraph, "(phi (< (var a \"int\") (var b \"int\")) 1 2)");
+        let mutant = add(&mut egraph, "(phi (<= (var a \"int\") (var b \"int\")) 1 2)");
+        let lt = add(&mut egraph, "(< (var a \"int\") (var b \"int\"))");
+        let lte = add(&mut egraph, "(<= (var a \"int\") (var b \"int\"))");
+        egraph.rebuild();
+        let point = find_point(&egraph, original, mutant).unwrap();
+        assert_eq!(point.path, vec![(egraph.find(original), 0)]);
+        assert_eq!((point.original, point.mutant), (egraph.find(lt), egraph.find(lte)));
+        assert!(find_point(&egraph, original, original).is_none());
+    }
+
+    #[test]
+    fn mutants_of_a_comparison_subsume_those_killed_by_more_outcomes() {
+        let mut egraph = EGraph::default();
+        let original = add(&mut egraph, "(< (var a \"int\") (var b \"int\"))");
+        let mutants = [
+            (1, "(<= (var a \"int\") (var b \"int\"))"),
+            (2, "(!= (var a \"int\") (var b \"int\"))"),
+            (3, "(== (var a \"int\") (var b \"int\"))"),
+            (4, "false"),
+            (5, "(> (var a \"int\") (var b \"int\"))"),
+            // The same comparison as the subject, which saturation would merge
+            (6, "(> (var b \"int\") (var a \"int\"))"),
+            (7, "true"),
+            (8, "(>= (var b \"int\") (var a \"int\"))"),
+            (9, "(! (< (var a \"int\") (var b \"int\")))"),
+        ];
+        let mut points = HashMap::new();
+        for (mid, expr) in mutants.iter() {
+            let mutant = add(&mut egraph, expr);
+            points.insert(*mid, MutationPoint { path: vec![], original, mutant });
+        }
+        egraph.rebuild();
+        let classes: Vec<HashSet<u32>> = (0..=9).map(|mid| [mid].iter().copied().collect()).collect();
+        let graph = subsumption_graph(&egraph, &classes, &points);
+
+        // 8 is 1 with its operands swapped, so they share a node. Mutants are
+        // killed by EQ (1, 8), GT (2), LT and EQ (3), LT (4), LT and GT (5),
+        // nothing (6), GT and EQ (7) and every ordered outcome (9)
+        assert_eq!(graph.nodes, vec![vec![1, 8], vec![2], vec![3], vec![4], vec![5], vec![6], vec![7], vec![9]]);
+        let mut edges = graph.edges.clone();
+        edges.sort();
+        assert_eq!(edges, vec![(0, 2), (0, 6), (1, 4), (1, 6), (2, 7), (3, 2), (3, 4), (4, 7), (6, 7)]);
+        assert_eq!(graph.dominators, vec![1, 2, 4, 6]);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::peg::{EGraph, Peg};
+use crate::rewrites::{Rule, Soundness};
+use crate::primitives::{JavaDouble, JavaFloat, JavaInt, JavaLong, JavaString};
+use egg::{Id, Language, RecExpr, Subst, Var};
+use std::fmt;
+
+/// The outcome of checking one rule against many valuations of its variables
+pub struct RuleReport {
+    pub name: String,
+    pub soundness: Soundness,
+    /// Valuations where the rule's condition held and both sides evaluated to
+    /// a constant
+    pub checked: usize,
+    /// Valuations where the condition held, but a side couldn't be evaluated
+    /// (for instance, operands of the wrong type), or where the rule's
+    /// patterns didn't instantiate
+    pub inconclusive: usize,
+    pub counterexamples: Vec<Counterexample>,
+}
+
+pub struct Counterexample {
+    pub valuation: Vec<(String, Peg)>,
+    pub lhs: Peg,
+    pub rhs: Peg,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let valuation: Vec<String> = self.valuation.iter()
+            .map(|(var, value)| format!("{} = {}", var, literal(value)))
+            .collect();
+        write!(f, "{}: lhs = {}, rhs = {}", valuation.join(", "), literal(&self.lhs), literal(&self.rhs))
+    }
+}
+
+impl RuleReport {
+    /// A rule tagged `Sound` for which a counterexample was found
+    pub fn is_mistagged(&self) -> bool {
+        self.soundness == Soundness::Sound && !self.counterexamples.is_empty()
+    }
+}
+
+/// Check each rule by evaluating both of its sides under every combination of
+/// the edge cases 0, ±1, MIN and MAX (for `float` and `double`, also -0.0,
+/// NaN and ±Infinity), then under `samples` random valuations, first with all
+/// variables `int`, then all `long`, `float`, `double` and `boolean`. Strings
+/// are checked too, alone and mixed with `int`s, since `+` concatenates them.
+/// All NaNs count as the same value.
+///
+/// A rule's condition is checked twice: once with its variables bound to their
+/// constants and once bound to opaque symbols. The rule counts as applying if
+/// either holds, since guards like `is_not_const` only exist to keep constant
+/// folding and rewriting from fighting, and never hold for constants.
+pub fn verify_rules(rules: &[Rule], samples: usize, seed: u64) -> Vec<RuleReport> {
+    let mut rng = XorShift::new(seed);
+    rules.iter().map(|rule| verify_rule(rule, samples, &mut rng)).collect()
+}
+
+fn verify_rule(rule: &Rule, samples: usize, rng: &mut XorShift) -> RuleReport {
+    let vars = pattern_vars(&rule.lhs);
+    let mut report = RuleReport {
+        name: rule.name().to_string(),
+        soundness: rule.soundness,
+        checked: 0,
+        inconclusive: 0,
+        counterexamples: vec![],
+    };
+
+    let int_edges: Vec<Peg> = [0, 1, -1, i32::MIN, i32::MAX].iter()
+        .map(|n| Peg::Num(JavaInt::from(*n)))
+        .collect();
+    let long_edges: Vec<Peg> = [0, 1, -1, i64::MIN, i64::MAX].iter()
+        .map(|n| Peg::Long(JavaLong::from(*n)))
+        .collect();
+    let float_edges: Vec<Peg> = [0.0, -0.0, 1.0, -1.0, f32::MIN, f32::MAX, f32::NAN, f32::INFINITY, f32::NEG_INFINITY]
+        .iter()
+        .map(|x| Peg::Float(JavaFloat::from(*x)))
+        .collect();
+    let double_edges: Vec<Peg> = [0.0, -0.0, 1.0, -1.0, f64::MIN, f64::MAX, f64::NAN, f64::INFINITY, f64::NEG_INFINITY]
+        .iter()
+        .map(|x| Peg::Double(JavaDouble::from(*x)))
+        .collect();
+    let bools = vec![Peg::Bool(false), Peg::Bool(true)];
+    let strings: Vec<Peg> = ["", "a", "b"].iter().map(|s| Peg::Str(JavaString::from(*s))).collect();
+    let strings_and_ints = vec![Peg::Str(JavaString::from("a")), Peg::Num(JavaInt::from(1))];
+
+    let mut valuations = vec![];
+    for values in &[int_edges, long_edges, float_edges, double_edges, bools, strings, strings_and_ints] {
+        valuations.extend(combinations(values, vars.len()));
+    }
+    for _ in 0..samples {
+        valuations.push(vars.iter().map(|_| Peg::Num(JavaInt::from(rng.next_int()))).collect());
+        valuations.push(vars.iter().map(|_| Peg::Long(JavaLong::from(rng.next_long()))).collect());
+        valuations.push(vars.iter().map(|_| Peg::Float(JavaFloat::from(rng.next_double() as f32))).collect());
+        valuations.push(vars.iter().map(|_| Peg::Double(JavaDouble::from(rng.next_double()))).collect());
+    }
+
+    for values in valuations {
+        let valuation: Vec<(String, Peg)> = vars.iter().cloned().zip(values).collect();
+        let outcome = condition_holds(rule, &valuation).and_then(|holds| {
+            if !holds {
+                return Ok(None);
+            }
+            Ok(Some((evaluate(&rule.lhs, &valuation)?, evaluate(&rule.rhs, &valuation)?)))
+        });
+        match outcome {
+            Ok(None) => (),
+            Ok(Some((Some(lhs), Some(rhs)))) => {
+                report.checked += 1;
+                if !same_value(&lhs, &rhs) {
+                    report.counterexamples.push(Counterexample { valuation, lhs, rhs });
+                }
+            }
+            Ok(Some(_)) | Err(_) => report.inconclusive += 1,
+        }
+    }
+    report
+}
+
+/// Whether `a` and `b` are the same Java value. Unlike `==` on `Peg`s, any two
+/// NaNs are: Java can't tell their bit patterns apart without converting them
+fn same_value(a: &Peg, b: &Peg) -> bool {
+    match (a, b) {
+        (Peg::Float(a), Peg::Float(b)) if a.value().is_nan() => b.value().is_nan(),
+        (Peg::Double(a), Peg::Double(b)) if a.value().is_nan() => b.value().is_nan(),
+        _ => a == b,
+    }
+}
+
+fn combinations(values: &[Peg], n: usize) -> Vec<Vec<Peg>> {
+    (0..n).fold(vec![vec![]], |partial, _| {
+        partial.iter()
+            .flat_map(|prefix| values.iter().map(move |v| {
+                let mut next = prefix.clone();
+                next.push(v.clone());
+                next
+            }))
+            .collect()
+    })
+}
+
+/// The distinct `?vars` of a pattern, in order of appearance
+pub(crate) fn pattern_vars(pattern: &str) -> Vec<String> {
+    let mut vars: Vec<String> = vec![];
+    for token in pattern.split(|c: char| c == '(' || c == ')' || c.is_whitespace()) {
+        if token.starts_with('?') && !vars.iter().any(|v| v == token) {
+            vars.push(token.to_string());
+        }
+    }
+    vars
+}
+
+/// Replace each `?var` in `pattern` with the text of its binding
+pub(crate) fn instantiate(pattern: &str, bindings: &[(String, String)]) -> String {
+    let mut result = String::new();
+    let mut token = String::new();
+    let flush = |token: &mut String, result: &mut String| {
+        match bindings.iter().find(|(var, _)| var == token) {
+            Some((_, text)) => result.push_str(text),
+            None => result.push_str(token),
+        }
+        token.clear();
+    };
+    for c in pattern.chars() {
+        if c == '(' || c == ')' || c.is_whitespace() {
+            flush(&mut token, &mut result);
+            result.push(c);
+        } else {
+            token.push(c);
+        }
+    }
+    flush(&mut token, &mut result);
+    result
+}
+
+/// The text of a constant, as it would appear in a pattern
+fn literal(value: &Peg) -> String {
+    match value {
+        Peg::Long(n) => format!("{}l", n),
+        _ => value.display_op().to_string(),
+    }
+}
+
+/// Add `pattern`, with its variables bound per `bindings`, to `egraph`
+fn add_instance(egraph: &mut EGraph, pattern: &str, bindings: &[(String, String)]) -> Result<Id, String> {
+    let expr: RecExpr<Peg> = instantiate(pattern, bindings)
+        .parse()
+        .map_err(|e| format!("Rule pattern {} doesn't instantiate: {}", pattern, e))?;
+    Ok(egraph.add_expr(&expr))
+}
+
+fn condition_holds(rule: &Rule, valuation: &[(String, Peg)]) -> Result<bool, String> {
+    let condition = match &rule.condition {
+        Some(condition) => condition,
+        None => return Ok(true),
+    };
+    let concrete: Vec<(String, String)> = valuation.iter()
+        .map(|(var, value)| (var.clone(), literal(value)))
+        .collect();
+    let symbolic: Vec<(String, String)> = valuation.iter()
+        .map(|(var, _)| (var.clone(), format!("sym-{}", &var[1..])))
+        .collect();
+    for bindings in &[concrete, symbolic] {
+        let mut egraph = EGraph::default();
+        let root = add_instance(&mut egraph, &rule.lhs, bindings)?;
+        let mut subst = Subst::default();
+        for (var, text) in bindings {
+            let var: Var = var.parse().unwrap();
+            let id = add_instance(&mut egraph, text, &[])?;
+            subst.insert(var, id);
+        }
+        egraph.rebuild();
+        let subst = canonical_subst(&egraph, &subst, bindings);
+        let root = egraph.find(root);
+        if condition(&mut egraph, root, &subst) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn canonical_subst(egraph: &EGraph, subst: &Subst, bindings: &[(String, String)]) -> Subst {
+    let mut canonical = Subst::default();
+    for (var, _) in bindings {
+        let var: Var = var.parse().unwrap();
+        canonical.insert(var, egraph.find(subst[var]));
+    }
+    canonical
+}
+
+/// The value `pattern` folds to under `valuation`. Throwing is a value too
+fn evaluate(pattern: &str, valuation: &[(String, Peg)]) -> Result<Option<Peg>, String> {
+    let bindings: Vec<(String, String)> = valuation.iter()
+        .map(|(var, value)| (var.clone(), literal(value)))
+        .collect();
+    let mut egraph = EGraph::default();
+    let root = add_instance(&mut egraph, pattern, &bindings)?;
+    egraph.rebuild();
+    let class = &egraph[root];
+    Ok(class.data.constant.clone().or_else(|| {
+        class.nodes.iter().find(|node| **node == Peg::Error).cloned()
+    }))
+}
+
+/// A small deterministic generator, so that runs are reproducible from a seed.
+/// Half of the values are small, where most boundary behavior lives
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero
+        XorShift((seed << 1) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn next_long(&mut self) -> i64 {
+        let x = self.next();
+        if x & 1 == 0 {
+            ((x >> 1) % 17) as i64 - 8
+        } else {
+            self.next() as i64
+        }
+    }
+
+    fn next_int(&mut self) -> i32 {
+        self.next_long() as i32
+    }
+
+    /// A value with a fractional part, or a small integer, or a large one
+    fn next_double(&mut self) -> f64 {
+        self.next_long() as f64 / 4.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, lhs: &str, rhs: &str) -> Rule {
+        Rule {
+            rewrite: egg::rewrite!(name; "(+ ?a 0)" => "?a"),
+            lhs: lhs.to_string(),
+            rhs: rhs.to_string(),
+            condition: None,
+            soundness: Soundness::Sound,
+        }
+    }
+
+    #[test]
+    fn floating_edges_are_sampled() {
+        let reports = verify_rules(&[rule("add-ident", "(+ ?a 0)", "?a")], 0, 1);
+        let valuations: Vec<String> = reports[0].counterexamples.iter()
+            .map(|counterexample| counterexample.to_string())
+            .collect();
+        assert_eq!(valuations, vec![
+            "?a = -0f: lhs = 0f, rhs = -0f",
+            "?a = -0d: lhs = 0d, rhs = -0d",
+            "?a = \"\": lhs = \"0\", rhs = \"\"",
+            "?a = \"a\": lhs = \"a0\", rhs = \"a\"",
+            "?a = \"b\": lhs = \"b0\", rhs = \"b\"",
+            // Again, among the strings mixed with ints
+            "?a = \"a\": lhs = \"a0\", rhs = \"a\"",
+        ]);
+        assert!(reports[0].is_mistagged());
+    }
+
+    #[test]
+    fn strings_are_sampled() {
+        let reports = verify_rules(&[rule("commute-add", "(+ ?a ?b)", "(+ ?b ?a)")], 10, 1);
+        let valuations: Vec<String> = reports[0].counterexamples.iter()
+            .map(|counterexample| counterexample.to_string())
+            .collect();
+        assert!(valuations.contains(&"?a = \"a\", ?b = \"b\": lhs = \"ab\", rhs = \"ba\"".to_string()));
+        assert!(valuations.contains(&"?a = \"a\", ?b = 1: lhs = \"a1\", rhs = \"1a\"".to_string()));
+
+        let purity = std::sync::Arc::new(crate::purity::PurityTable::builtin());
+        let rules = crate::rewrites::group_rules("arithmetic", &purity);
+        for report in verify_rules(&rules, 20, 1) {
+            assert!(!report.is_mistagged(), "{} has counterexamples", report.name);
+        }
+    }
+
+    #[test]
+    fn nans_are_the_same_value() {
+        let reports = verify_rules(&[rule("nan", "(/ 0.0d 0.0d)", "NaNd")], 10, 1);
+        assert_eq!(reports[0].counterexamples.len(), 0);
+        assert!(reports[0].checked > 0);
+        assert!(!same_value(&Peg::Double(JavaDouble::from(f64::NAN)), &Peg::Float(JavaFloat::from(f32::NAN))));
+    }
+
+    #[test]
+    fn comparisons_with_self_hold_for_nan() {
+        let purity = std::sync::Arc::new(crate::purity::PurityTable::builtin());
+        let rules = crate::rewrites::group_rules("comparison", &purity);
+        for report in verify_rules(&rules, 20, 1) {
+            assert!(!report.is_mistagged(), "{} has counterexamples", report.name);
+        }
+    }
+
+    #[test]
+    fn int_bitwise_identities_hold() {
+        let purity = std::sync::Arc::new(crate::purity::PurityTable::builtin());
+        let rules: Vec<Rule> = crate::rewrites::group_rules("bitwise", &purity)
+            .into_iter()
+            .filter(|rule| ["and-zero-int", "or-ones-int", "xor-self-int"].contains(&rule.name()))
+            .collect();
+        assert_eq!(rules.len(), 3);
+        for report in verify_rules(&rules, 20, 1) {
+            assert_eq!(report.soundness, Soundness::Sound);
+            assert!(report.checked > 0, "{} was never checked", report.name);
+            assert!(!report.is_mistagged(), "{} has counterexamples", report.name);
+        }
+    }
+
+    #[test]
+    fn patterns_that_dont_instantiate_are_inconclusive() {
+        let reports = verify_rules(&[rule("unbalanced", "(+ ?a 0", "?a")], 10, 1);
+        assert_eq!(reports[0].checked, 0);
+        assert!(reports[0].inconclusive > 0);
+        assert!(!reports[0].is_mistagged());
+    }
+}
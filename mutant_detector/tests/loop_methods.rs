@@ -0,0 +1,56 @@
+//! Detection on the methods of `test_subjects/LoopMethods`, whose mutants sit
+//! inside, or act on the results of, loops
+
+use mutant_detector::driver::parse_and_run;
+use mutant_detector::execution_config::ExecutionConfig;
+use mutant_detector::results::write_subjects_to_single_file;
+use mutant_detector::runtime_metrics::RuntimeMetrics;
+use std::fs;
+
+const SUBJECTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../test_subjects/LoopMethods/subjects.xml");
+
+/// The `.equiv-class` file written for LoopMethods under `config`
+fn equiv_classes(config: &ExecutionConfig, name: &str) -> String {
+    let subjects = parse_and_run(SUBJECTS, config, &mut RuntimeMetrics::default()).unwrap();
+    assert!(subjects.skipped.is_empty());
+    let file = std::env::temp_dir().join(format!("{}.equiv-class", name));
+    write_subjects_to_single_file(&subjects, file.to_str().unwrap()).unwrap();
+    let contents = fs::read_to_string(&file).unwrap();
+    fs::remove_file(&file).unwrap();
+    contents
+}
+
+// Subjects come in the order the serializer wrote them: countDown, invariant,
+// doubled and sum. Removing `x = k;` from invariant's loop (mutant 6) leaves `x`
+// at `k`, which it already was
+const EXPECTED: &str = "4\n5\n\n0 6\n7\n\n8\n\n1\n2\n3\n";
+
+#[test]
+fn loop_invariant_assignment_is_equivalent() {
+    assert_eq!(equiv_classes(&ExecutionConfig::default(), "loop_methods"), EXPECTED);
+}
+
+#[test]
+fn sound_rules_suffice_for_loop_invariants() {
+    let config = ExecutionConfig::default().set_exclude_unsound_rules(true);
+    assert_eq!(equiv_classes(&config, "loop_methods_sound"), EXPECTED);
+}
+
+#[test]
+fn isolated_subjects_agree() {
+    let config = ExecutionConfig::default().set_isolate_subjects(true);
+    assert_eq!(equiv_classes(&config, "loop_methods_isolated"), EXPECTED);
+}
+
+#[test]
+fn explanation_gives_the_rules_that_merged_the_loop_invariant() {
+    let config = ExecutionConfig::default().set_explain(true);
+    let subjects = parse_and_run(SUBJECTS, &config, &mut RuntimeMetrics::default()).unwrap();
+    let invariant = subjects.subjects.iter().find(|subj| subj.method == "LoopMethods@invariant(int,int)").unwrap();
+    let explanation = invariant.analysis_result.explanation.as_ref().unwrap();
+    assert!(explanation.contains("mutant 6 merged with the original"));
+    assert!(explanation.contains("[theta-self]"));
+    assert!(subjects.subjects.iter()
+        .filter(|subj| subj.method != invariant.method)
+        .all(|subj| subj.analysis_result.explanation.as_deref() == Some(format!("subject {}", subj.method).as_str())));
+}
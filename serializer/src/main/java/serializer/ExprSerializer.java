@@ -479,6 +479,8 @@ public class ExprSerializer {
           typeMap.put(k, PegNode.stringLit("java.io.Reader"));
         } else if (td.isBitSet()) {
           typeMap.put(k, PegNode.stringLit("java.util.BitSet"));
+        } else if (td.isPrimitive()) {
+          typeMap.put(k, PegNode.stringLit(td.typeName));
         }
         // TODO: Currently I am just hardcoding either "ARRAY" or "COLLECTION". At some point I want to
         //       use the following line to be completely general, but this will involve updating the
@@ -613,6 +615,15 @@ public class ExprSerializer {
     public boolean isBitSet() {
       return "java.util.BitSet".equals(typeName);
     }
+
+    public boolean isPrimitive() {
+      switch (typeName) {
+        case "boolean": case "byte": case "short": case "char": case "int": case "long": case "float": case "double":
+          return true;
+        default:
+          return false;
+      }
+    }
   }
 
   private String trimParseErrorMessage(String msg) {
@@ -166,7 +166,7 @@ public class FileSerializeDataCollector {
     final PegContext initCtx = PegContext.initWithParams(
             classVisitorResult.getFieldNames(),
             Util.getParameterList(decl),
-            new HashMap<>());
+            Util.getPrimitiveParameterTypes(decl));
     try {
       final PegContext ctx = decl.accept(stmtVisitor, initCtx).context;
       final VisitResult<?> vr = cvr.add(decl, ctx.asPeg().orElseThrow(IllegalStateException::new));
@@ -1,12 +1,14 @@
 package serializer;
 
 import com.github.javaparser.ast.CompilationUnit;
+import com.github.javaparser.ast.ImportDeclaration;
 import com.github.javaparser.ast.Node;
 import com.github.javaparser.ast.NodeList;
 import com.github.javaparser.ast.body.ClassOrInterfaceDeclaration;
 import com.github.javaparser.ast.body.ConstructorDeclaration;
 import com.github.javaparser.ast.body.MethodDeclaration;
 import com.github.javaparser.ast.body.Parameter;
+import com.github.javaparser.ast.body.TypeDeclaration;
 import com.github.javaparser.ast.nodeTypes.NodeWithParameters;
 import com.github.javaparser.ast.nodeTypes.NodeWithSimpleName;
 import com.github.javaparser.ast.nodeTypes.modifiers.NodeWithAccessModifiers;
@@ -14,6 +16,7 @@ import com.github.javaparser.ast.nodeTypes.modifiers.NodeWithStaticModifier;
 import com.github.javaparser.ast.type.ArrayType;
 import com.github.javaparser.ast.type.ClassOrInterfaceType;
 import com.github.javaparser.ast.type.Type;
+import serializer.peg.PegNode;
 
 import javax.swing.text.html.Option;
 import java.io.File;
@@ -186,6 +189,54 @@ public class Util {
     return params;
   }
 
+  /**
+   * @param name a simple name used at {@code at}
+   * @return if {@code name} resolves to a type there: one declared in, or imported by, {@code at}'s compilation
+   *     unit, or a class of {@code java.lang} or of a package the compilation unit imports on demand
+   */
+  public static boolean namesType(final String name, final Node at) {
+    final List<String> packages = new ArrayList<>();
+    packages.add("java.lang");
+    final Optional<CompilationUnit> cu = at.findCompilationUnit();
+    if (cu.isPresent()) {
+      for (ImportDeclaration imp : cu.get().getImports()) {
+        if (imp.isStatic()) continue;
+        if (imp.isAsterisk()) {
+          packages.add(imp.getNameAsString());
+        } else if (imp.getName().getIdentifier().equals(name)) {
+          return true;
+        }
+      }
+      if (cu.get().findAll(TypeDeclaration.class).stream().anyMatch(t -> t.getNameAsString().equals(name))) {
+        return true;
+      }
+    }
+    for (String pkg : packages) {
+      try {
+        Class.forName(pkg + "." + name, false, Util.class.getClassLoader());
+        return true;
+      } catch (ClassNotFoundException | LinkageError e) {
+        // not a class of this package
+      }
+    }
+    return false;
+  }
+
+  /**
+   * Map each parameter of {@code n} with a primitive type to a string literal naming the type, such as
+   * {@code "int"}. These become the type annotations of the parameters' {@code var} nodes, from which
+   * mutant_detector learns the range of their values.
+   */
+  public static Map<String, PegNode> getPrimitiveParameterTypes(NodeWithParameters<?> n) {
+    final Map<String, PegNode> types = new HashMap<>();
+    for (Parameter parameter : n.getParameters()) {
+      if (parameter.getType().isPrimitiveType() && !parameter.isVarArgs()) {
+        types.put(parameter.getNameAsString(), PegNode.stringLit(parameter.getType().asString()));
+      }
+    }
+    return types;
+  }
+
   /**
    * Decode an int string. Handles Decimal, hex and octal formats.
    *
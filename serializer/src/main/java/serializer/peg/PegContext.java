@@ -102,6 +102,17 @@ public class PegContext {
       return isField(key) && !localVariableLookup.containsKey(key);
     }
 
+    /**
+     * @param name a simple name used as the scope of a method call
+     * @return if {@code name} may refer to a class (e.g., {@code Math} in {@code Math.abs(x)}): it is capitalized, like
+     *         class names, and neither a local variable nor a field. Whether it names a type is up to the caller; see
+     *         {@link serializer.Util#namesType}
+     */
+    public boolean isClassName(final String name) {
+        return !name.isEmpty() && Character.isUpperCase(name.charAt(0))
+                && !localVariableLookup.containsKey(name) && !isField(name);
+    }
+
     /**
      * Lookup a key in the context. This key can correspond to a {@code parameter} or a {@code field}
      * @param key the method parameter or field name to look up in this context
@@ -180,9 +191,9 @@ public class PegContext {
         FieldAccessExpr fa = n;
 
         while (fa.getScope().isFieldAccessExpr()) {
-            derefs.insert(0, fa.getName());
-            derefs.insert(0, '.');
             fa = fa.getScope().toFieldAccessExpr().orElseThrow(() -> new RuntimeException("GetPathFail"));
+            derefs.insert(0, '.');
+            derefs.insert(0, fa.getName());
         }
         final ExpressionResult base = fa.getScope().accept(visitor, this);
         return PegNode.path(base.peg.id, derefs.toString()).exprResult(this);
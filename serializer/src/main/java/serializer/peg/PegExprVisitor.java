@@ -345,6 +345,13 @@ public class PegExprVisitor extends com.github.javaparser.ast.visitor.GenericVis
     public ExpressionResult visit(MethodCallExpr n, final PegContext context) {
         ExpressionResult scope;
 
+        if (n.getScope().isPresent()
+                && n.getScope().get().isNameExpr()
+                && context.isClassName(n.getScope().get().asNameExpr().getNameAsString())
+                && Util.namesType(n.getScope().get().asNameExpr().getNameAsString(), n)) {
+            return visitStaticCall(n.getScope().get().asNameExpr().getNameAsString(), n, context);
+        }
+
         if (n.getScope().isPresent()) {
             scope = n.getScope().get().accept(this, context);
             scope = scope.withExceptionCondition(PegNode.isnull(scope.peg.id),
@@ -375,6 +382,29 @@ public class PegExprVisitor extends com.github.javaparser.ast.visitor.GenericVis
         return PegNode.invokeToPeg(invocation.id).exprResult(ctx);
     }
 
+    /**
+     * Visit a call {@code ClassName.method(...)}. The method is named by its class, as written, so that calls to
+     * known library methods (e.g., {@code Math.abs}) can be recognized
+     */
+    private ExpressionResult visitStaticCall(final String className, final MethodCallExpr n, final PegContext context) {
+        final List<Integer> actualsPegs = new ArrayList<>();
+        PegContext ctx = context;
+
+        for (final Expression actual : n.getArguments()) {
+            final ExpressionResult er = actual.accept(this, ctx);
+            ctx = er.context;
+            actualsPegs.add(er.peg.id);
+        }
+        final PegNode actuals = PegNode.actuals(actualsPegs.toArray(new Integer[]{}));
+
+        final PegNode invocation = PegNode.invokeStatic(
+                ctx.heap.id,
+                className + "." + n.getNameAsString(),
+                actuals.id);
+        ctx = ctx.withHeap(PegNode.projectHeap(invocation.id));
+        return PegNode.invokeToPeg(invocation.id).exprResult(ctx);
+    }
+
     @Override
     public ExpressionResult visit(NullLiteralExpr n, PegContext arg) {
         return PegNode.nullLit().exprResult(arg);
@@ -462,12 +492,18 @@ public class PegExprVisitor extends com.github.javaparser.ast.visitor.GenericVis
 
     @Override
     public ExpressionResult visit(CharLiteralExpr n, PegContext arg) {
-        throw new RuntimeException("CharLiteralExpr");
+        return PegNode.charLit(n.asChar()).exprResult(arg);
     }
 
     @Override
     public ExpressionResult visit(DoubleLiteralExpr n, PegContext arg) {
-        throw new RuntimeException("DoubleLiteralExpr");
+        // DoubleLiteralExprs hold float literals too, which end with `f` or `F`. parseFloat and parseDouble
+        // accept the suffixes, but not underscores
+        final String value = n.getValue().replace("_", "");
+        if (value.endsWith("f") || value.endsWith("F")) {
+            return PegNode.floatLit(Float.parseFloat(value)).exprResult(arg);
+        }
+        return PegNode.doubleLit(Double.parseDouble(value)).exprResult(arg);
     }
 
     @Override
@@ -332,6 +332,137 @@ public abstract class PegNode {
         }
     }
 
+    /**
+     * A char literal, printed as in Java source: {@code 'a'}, with {@code \'} and {@code \\} escaped and
+     * any character outside of printable ASCII written as a four digit unicode escape
+     */
+    public final static class CharLit extends PegNode {
+        public final char value;
+        private CharLit(char value) {
+            this.value = value;
+            idLookup.put(this.id, this);
+            litLookup.put(value, this);
+        }
+
+        @Override
+        public <R, A> R accept(PegVisitor<R, A> visitor, A arg) {
+            return visitor.visit(this, arg);
+        }
+
+        @Override
+        public boolean isConst() {
+            return true;
+        }
+
+        @Override
+        public String toString() {
+            if (value == '\'' || value == '\\') {
+                return String.format("'\\%c'", value);
+            }
+            if (value >= 0x20 && value <= 0x7e) {
+                return String.format("'%c'", value);
+            }
+            return String.format("'\\u%04x'", (int) value);
+        }
+
+        @Override
+        public boolean equals(Object o) {
+            if (this == o) return true;
+            if (o == null || getClass() != o.getClass()) return false;
+            CharLit charLit = (CharLit) o;
+            return value == charLit.value;
+        }
+
+        @Override
+        public int hashCode() {
+            return Objects.hash(value);
+        }
+    }
+
+    /**
+     * A float literal, printed with an {@code f} suffix. The special values are {@code NaNf},
+     * {@code Infinityf} and {@code -Infinityf}
+     */
+    public final static class FloatLit extends PegNode {
+        public final float value;
+        private FloatLit(float value) {
+            this.value = value;
+            idLookup.put(this.id, this);
+            litLookup.put(value, this);
+        }
+
+        @Override
+        public <R, A> R accept(PegVisitor<R, A> visitor, A arg) {
+            return visitor.visit(this, arg);
+        }
+
+        @Override
+        public boolean isConst() {
+            return true;
+        }
+
+        @Override
+        public String toString() {
+            // Float.toString spells the special values NaN, Infinity and -Infinity
+            return Float.toString(value) + "f";
+        }
+
+        @Override
+        public boolean equals(Object o) {
+            if (this == o) return true;
+            if (o == null || getClass() != o.getClass()) return false;
+            FloatLit floatLit = (FloatLit) o;
+            return Float.compare(value, floatLit.value) == 0;
+        }
+
+        @Override
+        public int hashCode() {
+            return Objects.hash(value);
+        }
+    }
+
+    /**
+     * A double literal, printed with a {@code d} suffix. The special values are {@code NaNd},
+     * {@code Infinityd} and {@code -Infinityd}
+     */
+    public final static class DoubleLit extends PegNode {
+        public final double value;
+        private DoubleLit(double value) {
+            this.value = value;
+            idLookup.put(this.id, this);
+            litLookup.put(value, this);
+        }
+
+        @Override
+        public <R, A> R accept(PegVisitor<R, A> visitor, A arg) {
+            return visitor.visit(this, arg);
+        }
+
+        @Override
+        public boolean isConst() {
+            return true;
+        }
+
+        @Override
+        public String toString() {
+            // Double.toString spells the special values NaN, Infinity and -Infinity
+            return Double.toString(value) + "d";
+        }
+
+        @Override
+        public boolean equals(Object o) {
+            if (this == o) return true;
+            if (o == null || getClass() != o.getClass()) return false;
+            DoubleLit doubleLit = (DoubleLit) o;
+            return Double.compare(value, doubleLit.value) == 0;
+        }
+
+        @Override
+        public int hashCode() {
+            return Objects.hash(value);
+        }
+    }
+
     public static class OpNode extends PegNode {
         public final String op;
 
@@ -663,6 +794,27 @@ public abstract class PegNode {
         return litLookup.get(s);
     }
 
+    public static PegNode charLit(char c) {
+        if (!litLookup.containsKey(c)) {
+            return new CharLit(c);
+        }
+        return litLookup.get(c);
+    }
+
+    public static PegNode floatLit(float f) {
+        if (!litLookup.containsKey(f)) {
+            return new FloatLit(f);
+        }
+        return litLookup.get(f);
+    }
+
+    public static PegNode doubleLit(double d) {
+        if (!litLookup.containsKey(d)) {
+            return new DoubleLit(d);
+        }
+        return litLookup.get(d);
+    }
+
     public static PegNode unit() {
         return opNode("unit");
     }
@@ -715,6 +867,10 @@ public abstract class PegNode {
         return opNode("invoke", heap, receiver, opNode(method).id, actuals);
     }
 
+    public static PegNode invokeStatic(Integer heap, String method, Integer actuals) {
+        return opNode("invoke-static", heap, opNode("method", opNode(method).id).id, actuals);
+    }
+
     public static PegNode actuals(Integer...actuals) {
         return opNode("actuals", actuals);
     }
@@ -69,6 +69,21 @@ public class PegPrinter {
       return String.format("(string-lit %s)", node.value);
     }
 
+    @Override
+    protected String combine(PegNode.CharLit node, Void arg) {
+      return String.format("(char-lit %s)", node);
+    }
+
+    @Override
+    protected String combine(PegNode.FloatLit node, Void arg) {
+      return String.format("(float-lit %s)", node);
+    }
+
+    @Override
+    protected String combine(PegNode.DoubleLit node, Void arg) {
+      return String.format("(double-lit %s)", node);
+    }
+
     @Override
     protected String combine(PegNode.OpNode node, Void arg, List<String> children) {
       StringBuilder sb = new StringBuilder("(");
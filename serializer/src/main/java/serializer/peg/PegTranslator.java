@@ -104,7 +104,7 @@ public class PegTranslator {
         final PegContext initCtx = PegContext.initWithParams(
                 classVisitorResult.getFieldNames(),
                 Util.getParameterList(n),
-                new HashMap<>());
+                Util.getPrimitiveParameterTypes(n));
         final ExpressionResult result = n.accept(stmtVisitor, initCtx);
         return result.context.asPeg().orElseThrow(IllegalStateException::new);
     }
@@ -150,7 +150,6 @@ public class SimpleValidator extends VoidVisitorAdapter<Set<MethodDeclaration>>
     @Override
     public void visit(CharLiteralExpr n, Set<MethodDeclaration> arg) {
         super.visit(n, arg);
-        throw new SimpleValidationException("Simple doesn't support Chars");
     }
 
     @Override
@@ -200,7 +199,6 @@ public class SimpleValidator extends VoidVisitorAdapter<Set<MethodDeclaration>>
     @Override
     public void visit(DoubleLiteralExpr n, Set<MethodDeclaration> arg) {
         super.visit(n, arg);
-        throw new SimpleValidationException("Simple doesn't support DoubleLiteralExprs");
     }
 
     @Override
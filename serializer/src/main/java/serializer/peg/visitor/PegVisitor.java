@@ -132,6 +132,36 @@ public class PegVisitor<R, A> {
     return table.get(node);
   }
 
+  public R visit(final PegNode.CharLit node, final A arg) {
+    if (table.containsKey(node)) {
+      return table.get(node);
+    }
+    preVisit(node, arg);
+
+    table.put(node, combine(node, arg));
+    return table.get(node);
+  }
+
+  public R visit(final PegNode.FloatLit node, final A arg) {
+    if (table.containsKey(node)) {
+      return table.get(node);
+    }
+    preVisit(node, arg);
+
+    table.put(node, combine(node, arg));
+    return table.get(node);
+  }
+
+  public R visit(final PegNode.DoubleLit node, final A arg) {
+    if (table.containsKey(node)) {
+      return table.get(node);
+    }
+    preVisit(node, arg);
+
+    table.put(node, combine(node, arg));
+    return table.get(node);
+  }
+
   public R visit(final PegNode.ThetaNode node, final A arg) {
     if (table.containsKey(node)) {
       return table.get(node);
@@ -152,6 +182,9 @@ public class PegVisitor<R, A> {
   protected void preVisit(final PegNode.LongLit node, final A arg) {}
   protected void preVisit(final PegNode.BoolLit node, final A arg) {}
   protected void preVisit(final PegNode.StringLit node, final A arg) {}
+  protected void preVisit(final PegNode.CharLit node, final A arg) {}
+  protected void preVisit(final PegNode.FloatLit node, final A arg) {}
+  protected void preVisit(final PegNode.DoubleLit node, final A arg) {}
 
   protected R combine(final PegNode.OpNode node, final A arg, final List<R> children) {
     return null;
@@ -184,4 +217,16 @@ public class PegVisitor<R, A> {
   protected R combine(final PegNode.StringLit node, final A arg) {
     return null;
   }
+
+  protected R combine(final PegNode.CharLit node, final A arg) {
+    return null;
+  }
+
+  protected R combine(final PegNode.FloatLit node, final A arg) {
+    return null;
+  }
+
+  protected R combine(final PegNode.DoubleLit node, final A arg) {
+    return null;
+  }
 }
@@ -53,6 +53,8 @@ public class XMLGenerator {
 
         // No longer needed since subjects is cut out of spec
         subjects = document.createElement("subjects");
+        // Must match SUBJECTS_SCHEMA_VERSION in mutant_detector/src/driver.rs
+        subjects.setAttribute("version", "1");
         document.appendChild(subjects);
     }
 
@@ -0,0 +1,78 @@
+package serializer.peg;
+
+import com.github.javaparser.StaticJavaParser;
+import com.github.javaparser.ast.expr.MethodCallExpr;
+import org.junit.Test;
+
+import static org.junit.Assert.*;
+
+/**
+ * Literals must be printed with the spellings that mutant_detector parses, and only scopes naming a type make
+ * static calls
+ */
+public class PegExprVisitorTest {
+  final PegExprVisitor pev = new PegExprVisitor();
+
+  private String translate(final String expression) {
+    return StaticJavaParser.parseExpression(expression).accept(pev, PegContext.EMPTY_CTX).peg.toDerefString();
+  }
+
+  @Test
+  public void testCharLiterals() {
+    assertEquals("'a'", translate("'a'"));
+    assertEquals("'\"'", translate("'\"'"));
+    assertEquals("'\\''", translate("'\\''"));
+    assertEquals("'\\\\'", translate("'\\\\'"));
+    assertEquals("'\\u000a'", translate("'\\n'"));
+    assertEquals("'\\u00e9'", translate("'\\u00e9'"));
+  }
+
+  @Test
+  public void testFloatLiterals() {
+    assertEquals("1.5f", translate("1.5f"));
+    assertEquals("1.0E10f", translate("1e10F"));
+    assertEquals("1000.0f", translate("1_000f"));
+    assertEquals("NaNf", PegNode.floatLit(Float.NaN).toString());
+    assertEquals("Infinityf", PegNode.floatLit(Float.POSITIVE_INFINITY).toString());
+    assertEquals("-Infinityf", PegNode.floatLit(Float.NEGATIVE_INFINITY).toString());
+    assertEquals("-0.0f", PegNode.floatLit(-0.0f).toString());
+  }
+
+  @Test
+  public void testDoubleLiterals() {
+    assertEquals("1.5d", translate("1.5"));
+    assertEquals("2.0d", translate("2d"));
+    assertEquals("1.0E-5d", translate("1e-5"));
+    assertEquals("8.0d", translate("0x1p3"));
+    assertEquals("NaNd", PegNode.doubleLit(Double.NaN).toString());
+    assertEquals("Infinityd", PegNode.doubleLit(Double.POSITIVE_INFINITY).toString());
+    assertEquals("-Infinityd", PegNode.doubleLit(Double.NEGATIVE_INFINITY).toString());
+  }
+
+  /**
+   * Translate the first method call of the compilation unit {@code source}
+   */
+  private String translateCall(final String source) {
+    final MethodCallExpr call = StaticJavaParser.parse(source).findFirst(MethodCallExpr.class).get();
+    return call.accept(pev, PegContext.EMPTY_CTX).peg.toDerefString();
+  }
+
+  @Test
+  public void testStaticCallScopes() {
+    assertTrue(translateCall("class A { int f() { return Math.abs(1); } }").contains("invoke-static"));
+    assertTrue(translateCall("import java.util.*; class A { Object f() { return Collections.emptyList(); } }")
+            .contains("invoke-static"));
+    assertTrue(translateCall("import a.b.Foo; class A { int f() { return Foo.g(); } }").contains("invoke-static"));
+    assertTrue(translateCall("class A { int f() { return B.g(); } } class B { static int g() { return 0; } }")
+            .contains("invoke-static"));
+    assertFalse(translateCall("class A { int f() { return list.size(); } }").contains("invoke-static"));
+    assertFalse(translateCall("class A { int f() { return Unknown.size(); } }").contains("invoke-static"));
+  }
+
+  @Test
+  public void testFloatAndDoubleLiteralsAreDistinct() {
+    assertNotEquals(PegNode.floatLit(1.5f).id, PegNode.doubleLit(1.5).id);
+    assertNotEquals(PegNode.doubleLit(0.0).id, PegNode.doubleLit(-0.0).id);
+    assertEquals(PegNode.doubleLit(Double.NaN).id, PegNode.doubleLit(Double.NaN).id);
+  }
+}
@@ -0,0 +1,37 @@
+public class LoopMethods {
+
+    int sum(int n) {
+        int sum = 0;
+        for (int i = 0; i != n; i++) {
+            sum = sum + i;
+        }
+        return sum;
+    }
+
+    int countDown(int n) {
+        int steps = 0;
+        while (n > 0) {
+            n = n - 1;
+            steps = steps + 1;
+        }
+        return steps;
+    }
+
+    int invariant(int n, int k) {
+        int x = k;
+        for (int i = 0; i < n; i++) {
+            x = k;
+        }
+        return x * 2;
+    }
+
+    int doubled(int n) {
+        int a = 0;
+        int b = 0;
+        for (int i = 0; i < n; i++) {
+            a = a + 1;
+            b = b + 1;
+        }
+        return a + b;
+    }
+}